@@ -0,0 +1,424 @@
+//! Write parsed Avro schemas back as Avdl source.
+//!
+//! `to_avdl` emits a schema file, a `namespace` statement followed by the named
+//! types declared inside the schema, nested types first so they are declared
+//! before being used. Types of the file namespace are referenced by their
+//! name, the others by their fullname. Named types of another namespace than
+//! the file one are declared with a `@namespace` annotation.
+//! `to_avdl_with_options` takes the `WriteOptions` of a parsed protocol, to
+//! write float defaults declared as integers, like `double x = 5;`, back so.
+
+use apache_avro::schema::{
+    Alias, DecimalSchema, EnumSchema, FixedSchema, Name, Namespace, RecordField, RecordFieldOrder,
+    RecordSchema, Schema,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::parser::{decimal_text, latin1_bytes};
+use crate::protocol::{integral_default, WriteOptions};
+
+#[derive(Default)]
+struct AvdlWriter {
+    definitions: Vec<String>,
+    seen: Vec<Name>,
+    namespace: Namespace,
+    options: WriteOptions,
+}
+
+/// Emit an Avdl schema file declaring `schema` and every named type declared inside it.
+pub fn to_avdl(schema: &Schema) -> String {
+    to_avdl_with_options(schema, &WriteOptions::default())
+}
+
+/// Emit an Avdl schema file as [`to_avdl`] does, written with `options`.
+pub fn to_avdl_with_options(schema: &Schema, options: &WriteOptions) -> String {
+    let mut writer = AvdlWriter {
+        namespace: namespace_of(schema),
+        options: options.clone(),
+        ..Default::default()
+    };
+    writer.collect(schema, &None);
+
+    let mut out = String::new();
+    if let Some(namespace) = &writer.namespace {
+        out.push_str(&format!("namespace {namespace};\n"));
+    }
+    for definition in writer.definitions {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&definition);
+    }
+    out
+}
+
+fn namespace_of(schema: &Schema) -> Namespace {
+    match schema {
+        Schema::Record(RecordSchema { name, .. })
+        | Schema::Enum(EnumSchema { name, .. })
+        | Schema::Fixed(FixedSchema { name, .. }) => name.namespace.clone(),
+        _ => None,
+    }
+}
+
+impl AvdlWriter {
+    // Walk the schema depth first, so nested types are declared before the
+    // records using them. Nested types without a namespace take the one of the
+    // enclosing record.
+    fn collect(&mut self, schema: &Schema, enclosing_namespace: &Namespace) {
+        match schema {
+            Schema::Record(record) => {
+                let name = record.name.fully_qualified_name(enclosing_namespace);
+                if self.seen.contains(&name) {
+                    return;
+                }
+                self.seen.push(name.clone());
+                for field in &record.fields {
+                    self.collect(&field.schema, &name.namespace);
+                }
+                let definition = self.record(record, &name);
+                self.definitions.push(definition);
+            }
+            Schema::Enum(enum_schema) => {
+                let name = enum_schema.name.fully_qualified_name(enclosing_namespace);
+                if self.seen.contains(&name) {
+                    return;
+                }
+                let definition = self.enumeration(enum_schema, &name);
+                self.seen.push(name);
+                self.definitions.push(definition);
+            }
+            Schema::Fixed(fixed) => {
+                let name = fixed.name.fully_qualified_name(enclosing_namespace);
+                if self.seen.contains(&name) {
+                    return;
+                }
+                let definition = self.fixed_declaration(fixed, &name);
+                self.seen.push(name);
+                self.definitions.push(definition);
+            }
+            Schema::Array(schema) | Schema::Map(schema) => {
+                self.collect(schema, enclosing_namespace)
+            }
+            Schema::Union(union_schema) => {
+                for variant in union_schema.variants() {
+                    self.collect(variant, enclosing_namespace);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn record(&self, record: &RecordSchema, name: &Name) -> String {
+        let mut out = doc_comment(&record.doc, "");
+        out.push_str(&annotations(&record.attributes, &[]));
+        out.push_str(&self.namespace_annotation(name));
+        out.push_str(&aliases(&record.aliases, "\n"));
+        out.push_str(&format!("record {} {{\n", record.name.name));
+        for field in &record.fields {
+            out.push_str(&self.field(field, name));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    // Sample:
+    // ```
+    //   /** doc */
+    //   @java-class("java.util.Date") long @order("ignore") @aliases(["old"]) ts = 0;
+    // ```
+    fn field(&self, field: &RecordField, record: &Name) -> String {
+        let enclosing_namespace = &record.namespace;
+        let mut out = doc_comment(&field.doc, "  ");
+        out.push_str("  ");
+        out.push_str(&annotations(&field.custom_attributes, &[]).replace('\n', " "));
+        out.push_str(&self.type_name(&field.schema, enclosing_namespace));
+        match field.order {
+            RecordFieldOrder::Ascending => {}
+            RecordFieldOrder::Descending => out.push_str(" @order(\"descending\")"),
+            RecordFieldOrder::Ignore => out.push_str(" @order(\"ignore\")"),
+        }
+        if let Some(field_aliases) = &field.aliases {
+            out.push_str(&format!(" @aliases({})", json_strings(field_aliases)));
+        }
+        out.push_str(&format!(" {}", field.name));
+        if let Some(default) = &field.default {
+            let default = if self.options.is_integral_default(record, &field.name) {
+                integral_default(default)
+            } else {
+                default.clone()
+            };
+            out.push_str(&format!(" = {}", default_literal(&field.schema, &default)));
+        }
+        out.push_str(";\n");
+        out
+    }
+
+    // The Avdl type of a field, or of an item of a collection
+    fn type_name(&self, schema: &Schema, enclosing_namespace: &Namespace) -> String {
+        match schema {
+            Schema::Null => String::from("null"),
+            Schema::Boolean => String::from("boolean"),
+            Schema::Int => String::from("int"),
+            Schema::Long => String::from("long"),
+            Schema::Float => String::from("float"),
+            Schema::Double => String::from("double"),
+            Schema::Bytes => String::from("bytes"),
+            Schema::String => String::from("string"),
+            Schema::Array(items) => {
+                format!("array<{}>", self.type_name(items, enclosing_namespace))
+            }
+            Schema::Map(values) => format!("map<{}>", self.type_name(values, enclosing_namespace)),
+            Schema::Union(union_schema) => {
+                let variants: Vec<String> = union_schema
+                    .variants()
+                    .iter()
+                    .map(|variant| self.type_name(variant, enclosing_namespace))
+                    .collect();
+                format!("union {{ {} }}", variants.join(", "))
+            }
+            Schema::Record(RecordSchema { name, .. })
+            | Schema::Enum(EnumSchema { name, .. })
+            | Schema::Fixed(FixedSchema { name, .. })
+            | Schema::Ref { name } => {
+                let name = name.fully_qualified_name(enclosing_namespace);
+                if name.namespace == self.namespace {
+                    name.name
+                } else {
+                    name.fullname(None)
+                }
+            }
+            Schema::Decimal(decimal) => {
+                format!("decimal({}, {})", decimal.precision, decimal.scale)
+            }
+            Schema::Uuid => String::from("uuid"),
+            Schema::Date => String::from("date"),
+            Schema::TimeMillis => String::from("time_ms"),
+            Schema::TimestampMillis => String::from("timestamp_ms"),
+            Schema::TimeMicros => String::from("time_us"),
+            Schema::TimestampMicros => String::from("timestamp_us"),
+            Schema::Duration => String::from("bytes"),
+        }
+    }
+
+    fn enumeration(&self, enum_schema: &EnumSchema, name: &Name) -> String {
+        let mut out = doc_comment(&enum_schema.doc, "");
+        out.push_str(&annotations(&enum_schema.attributes, &["symbolDocs"]));
+        out.push_str(&self.namespace_annotation(name));
+        out.push_str(&aliases(&enum_schema.aliases, "\n"));
+        out.push_str(&format!("enum {} {{\n", enum_schema.name.name));
+        let symbol_docs = enum_schema.attributes.get("symbolDocs");
+        let symbols: Vec<String> = enum_schema
+            .symbols
+            .iter()
+            .map(|symbol| {
+                let doc = symbol_docs
+                    .and_then(|docs| docs.get(symbol))
+                    .and_then(Value::as_str)
+                    .map(String::from);
+                format!("{}  {symbol}", doc_comment(&doc, "  "))
+            })
+            .collect();
+        out.push_str(&symbols.join(",\n"));
+        out.push_str("\n}");
+        if let Some(default) = &enum_schema.default {
+            out.push_str(&format!(" = {default};"));
+        }
+        out.push('\n');
+        out
+    }
+
+    fn fixed_declaration(&self, fixed: &FixedSchema, name: &Name) -> String {
+        let mut out = doc_comment(&fixed.doc, "");
+        out.push_str(&annotations(&fixed.attributes, &[]));
+        out.push_str(&self.namespace_annotation(name));
+        out.push_str(&format!(
+            "fixed {}{}({});\n",
+            aliases(&fixed.aliases, " "),
+            fixed.name.name,
+            fixed.size
+        ));
+        out
+    }
+
+    // Named types outside the file namespace carry their own
+    fn namespace_annotation(&self, name: &Name) -> String {
+        match &name.namespace {
+            Some(namespace) if name.namespace != self.namespace => {
+                format!("@namespace(\"{namespace}\")\n")
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+// Custom annotations, one per line, leaving out the `skipped` ones written
+// some other way
+fn annotations(attributes: &BTreeMap<String, Value>, skipped: &[&str]) -> String {
+    attributes
+        .iter()
+        .filter(|(key, _)| !skipped.contains(&key.as_str()))
+        .map(|(key, value)| format!("@{key}({value})\n"))
+        .collect()
+}
+
+fn aliases(aliases: &Option<Vec<Alias>>, separator: &str) -> String {
+    match aliases {
+        Some(aliases) => {
+            let fullnames: Vec<String> = aliases.iter().map(|a| a.fullname(None)).collect();
+            format!("@aliases({}){separator}", json_strings(&fullnames))
+        }
+        None => String::new(),
+    }
+}
+
+fn json_strings(values: &[String]) -> String {
+    Value::from(values.to_vec()).to_string()
+}
+
+// Enum symbols are written bare, any other default as JSON. Bytes and fixed
+// are strings of a code point up to `\u00ff` for each byte, as in `.avsc`.
+fn default_literal(schema: &Schema, default: &Value) -> String {
+    let schema = match schema {
+        Schema::Union(union_schema) => &union_schema.variants()[0],
+        schema => schema,
+    };
+    match (schema, default) {
+        (Schema::Enum(_) | Schema::Ref { .. }, Value::String(symbol)) => symbol.clone(),
+        _ => decimal_numbers(schema, default).to_string(),
+    }
+}
+
+// Decimals are held as the string of their bytes, Avdl reads a string like
+// `"12.34"` as a number, so they are written as the string of their number
+fn decimal_numbers(schema: &Schema, default: &Value) -> Value {
+    match (schema, default) {
+        (Schema::Decimal(DecimalSchema { scale, .. }), Value::String(bytes)) => {
+            match latin1_bytes(bytes) {
+                Ok(bytes) => Value::from(decimal_text(&bytes, *scale)),
+                Err(_) => default.clone(),
+            }
+        }
+        (Schema::Array(items), Value::Array(values)) => values
+            .iter()
+            .map(|value| decimal_numbers(items, value))
+            .collect(),
+        (Schema::Map(values), Value::Object(entries)) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), decimal_numbers(values, value)))
+                .collect(),
+        ),
+        (Schema::Union(union_schema), value) => match union_schema.variants().first() {
+            Some(first) => decimal_numbers(first, value),
+            None => default.clone(),
+        },
+        _ => default.clone(),
+    }
+}
+
+fn doc_comment(doc: &Option<String>, indent: &str) -> String {
+    match doc {
+        Some(doc) => format!("{indent}/** {doc} */\n"),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_to_avdl_round_trip() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            /** Status of a customer */
+            enum Status {
+                /** Can buy */
+                ACTIVE,
+                INACTIVE
+            } = ACTIVE;
+            fixed @aliases(["org.example.Hash"]) MD5(16);
+            @aliases(["org.old.Client"])
+            record Customer {
+                /** Full name */
+                string name;
+                long @order("descending") id = 1;
+                Status status = INACTIVE;
+                array<string> @aliases(["labels"]) tags = ["new"];
+                map<int> counts = {"a": 1};
+                union { null, string } nickname = null;
+                union { int, MD5 } code;
+                MD5 hash = "0123456789abcdeÿ";
+                bytes data = "ab\u0000";
+                decimal(10, 2) price = "12.34";
+                array<decimal(10, 2)> prices = [-1.5, 0.5];
+                map<union { decimal(4, 0), null }> totals = {"a": 1};
+                timestamp_ms created;
+                @logicalType("timestamp-micros") long updated;
+                time_us elapsed;
+                @java-class("java.util.Date") long seen;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+        let customer = &schemas[2];
+
+        let out = to_avdl(customer);
+        let (tail, reparsed) = parse(&out).unwrap();
+        assert_eq!(tail, "");
+        let reparsed = reparsed.last().unwrap();
+        assert_eq!(reparsed, customer);
+        assert_eq!(
+            serde_json::to_value(reparsed).unwrap(),
+            serde_json::to_value(customer).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_avdl_other_namespace() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            @namespace("org.other") enum Color { RED, BLUE }
+            @namespace("org.other") fixed Hash(4);
+            record Item {
+                org.other.Color color = RED;
+                org.other.Hash hash;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+        let item = &schemas[2];
+
+        let out = to_avdl(item);
+        assert!(out.contains("@namespace(\"org.other\")\nenum Color {"));
+        assert!(out.contains("@namespace(\"org.other\")\nfixed Hash(4);"));
+        let (_tail, reparsed) = parse(&out).unwrap();
+        assert_eq!(reparsed.last().unwrap(), item);
+    }
+
+    #[test]
+    fn test_to_avdl_integral_default() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            record Item {
+                double price = 5;
+                union { float, null } weight = 2;
+                double ratio = 0.5;
+                double discount = 1.0;
+            }
+        }"#;
+        let protocol = crate::parser::parse_idl(input).unwrap();
+        let item = &protocol.types[0];
+
+        let out = to_avdl(item);
+        assert!(out.contains(" price = 5.0;"));
+        let out = to_avdl_with_options(item, &protocol.write_options());
+        assert!(out.contains(" price = 5;"));
+        assert!(out.contains(" weight = 2;"));
+        assert!(out.contains(" ratio = 0.5;"));
+        assert!(out.contains(" discount = 1.0;"));
+        let (_tail, reparsed) = parse(&out).unwrap();
+        assert_eq!(reparsed.last().unwrap(), item);
+    }
+}
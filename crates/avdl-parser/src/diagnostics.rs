@@ -0,0 +1,111 @@
+//! Machine readable results for editors and CI.
+//!
+//! `parse_and_report` never fails, the outcome of the parse is described by a
+//! `Report` which serializes to JSON with `serde_json`:
+//!
+//! ```json
+//! {
+//!   "diagnostics": [
+//!     { "severity": "error", "message": "Duplicate field name", "line": 4, "column": 13, "length": 3 }
+//!   ],
+//!   "schemas": null
+//! }
+//! ```
+
+use apache_avro::schema::Schema;
+use serde::Serialize;
+
+use crate::parser::{parse_schemas, strip_bom, ParseError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the source. `line` and `column` are 1-based, `column` and
+/// `length` are counted in characters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+    /// The parsed schemas, `None` when the source has errors.
+    pub schemas: Option<Vec<Schema>>,
+}
+
+/// Parse `input` and describe the outcome as a `Report`.
+pub fn parse_and_report(input: &str) -> Report {
+    // Offsets of errors are counted without the byte order mark
+    let input = strip_bom(input);
+    match parse_schemas(input) {
+        Ok(schemas) => Report {
+            diagnostics: vec![],
+            schemas: Some(schemas),
+        },
+        Err(e) => Report {
+            diagnostics: vec![diagnostic(input, e)],
+            schemas: None,
+        },
+    }
+}
+
+fn diagnostic(input: &str, error: ParseError) -> Diagnostic {
+    // Highlight the token where the parser stopped
+    let length = input[error.offset..]
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .next()
+        .map(|token| token.chars().count())
+        .unwrap_or(0);
+
+    Diagnostic {
+        severity: Severity::Error,
+        message: error.message,
+        line: error.line,
+        column: error.column,
+        length,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_report_error() {
+        let input =
+            "protocol MyProtocol {\n  record Hello {\n    string name;\n    int name;\n  }\n}";
+        let report = parse_and_report(input);
+        let out = serde_json::to_value(&report).unwrap();
+
+        let expected = json!({
+            "diagnostics": [{
+                "severity": "error",
+                "message": "Duplicate field name",
+                "line": 4,
+                "column": 5,
+                "length": 3
+            }],
+            "schemas": null
+        });
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_parse_and_report_ok() {
+        let input = "protocol MyProtocol {\n  record Hello {\n    string name;\n  }\n}";
+        let report = parse_and_report(input);
+
+        assert!(report.diagnostics.is_empty());
+        assert_eq!(report.schemas.map(|s| s.len()), Some(1));
+    }
+}
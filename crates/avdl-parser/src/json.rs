@@ -0,0 +1,405 @@
+//! Write parsed schemas and protocols as the Avro JSON of `.avsc` and `.avpr`
+//! files.
+//!
+//! `parse` inlines every named type where it is used, so the same type can
+//! appear many times. Avro only accepts a named type to be declared once,
+//! it is written in full where it first appears and referenced by its
+//! fullname afterwards.
+//!
+//! Float defaults declared as integers, like `double x = 5;`, are written as
+//! `5.0` unless the `WriteOptions` of the protocol say otherwise, `to_avpr`
+//! uses the ones of the protocol it writes.
+
+use apache_avro::schema::{Name, Namespace, RecordFieldOrder, RecordSchema, Schema};
+use serde_json::{json, Map, Value};
+
+use crate::parser::{named_type_name, reference_declared};
+use crate::protocol::{integral_default, Message, Protocol, WriteOptions};
+
+/// Write `schemas` as the pretty printed JSON of an `.avsc` file. A single
+/// schema is written as is, several as a JSON array, the union of the ones not
+/// already declared inside a previous one.
+pub fn to_avsc(schemas: &[Schema]) -> Result<String, serde_json::Error> {
+    to_avsc_with_options(schemas, &WriteOptions::default())
+}
+
+/// Write `schemas` as [`to_avsc`] does, with `options`.
+pub fn to_avsc_with_options(
+    schemas: &[Schema],
+    options: &WriteOptions,
+) -> Result<String, serde_json::Error> {
+    let mut declared = Vec::new();
+    let mut written = Vec::new();
+    // References are left by `ParseOptions::reference_named_types` for the types
+    // already written inside a previous one
+    for schema in schemas.iter().filter(|s| !matches!(s, Schema::Ref { .. })) {
+        let name = named_type_name(schema, &None);
+        if name.is_some_and(|name| declared.contains(&name)) {
+            continue;
+        }
+        written.push(reference_declared(schema, &mut declared, &None));
+    }
+
+    let written = written
+        .iter()
+        .map(|schema| schema_json(schema, options, &None))
+        .collect::<Result<Vec<Value>, serde_json::Error>>()?;
+    match written.as_slice() {
+        [schema] => serde_json::to_string_pretty(schema),
+        schemas => serde_json::to_string_pretty(schemas),
+    }
+}
+
+/// Write `protocol` as the pretty printed JSON of an `.avpr` file. Messages
+/// reference the types of the protocol by their fullname.
+pub fn to_avpr(protocol: &Protocol) -> Result<String, serde_json::Error> {
+    let options = protocol.write_options();
+    let mut declared = Vec::new();
+    let mut types = Vec::new();
+    for schema in protocol.types.iter() {
+        if let Schema::Ref { .. } = schema {
+            continue;
+        }
+        let mut value = schema_json(
+            &reference_declared(schema, &mut declared, &protocol.namespace),
+            &options,
+            &protocol.namespace,
+        )?;
+        // `apache_avro` has no error records, they are written as records
+        if protocol.is_error(schema) {
+            value["type"] = Value::from("error");
+        }
+        types.push(value);
+    }
+
+    let mut messages = Map::new();
+    for message in protocol.messages.iter() {
+        messages.insert(
+            message.name.clone(),
+            message_json(message, &mut declared, &protocol.namespace, &options)?,
+        );
+    }
+
+    let mut out = json!({ "protocol": protocol.name });
+    if let Some(namespace) = &protocol.namespace {
+        out["namespace"] = Value::from(namespace.as_str());
+    }
+    if let Some(doc) = &protocol.doc {
+        out["doc"] = Value::from(doc.as_str());
+    }
+    out["types"] = Value::Array(types);
+    out["messages"] = Value::Object(messages);
+    serde_json::to_string_pretty(&out)
+}
+
+fn message_json(
+    message: &Message,
+    declared: &mut Vec<Name>,
+    namespace: &Namespace,
+    options: &WriteOptions,
+) -> Result<Value, serde_json::Error> {
+    let mut out = Map::new();
+    if let Some(doc) = &message.doc {
+        out.insert(String::from("doc"), Value::from(doc.as_str()));
+    }
+    let mut request = Vec::new();
+    for param in message.request.iter() {
+        let schema = reference_declared(&param.schema, declared, namespace);
+        let mut value =
+            json!({ "name": param.name, "type": schema_json(&schema, options, namespace)? });
+        if let Some(default) = &param.default {
+            value["default"] = if message.integral_defaults.contains(&param.name) {
+                integral_default(default)
+            } else {
+                default.clone()
+            };
+        }
+        if let Some(aliases) = &param.aliases {
+            value["aliases"] = Value::from(aliases.clone());
+        }
+        request.push(value);
+    }
+    out.insert(String::from("request"), Value::Array(request));
+    let response = reference_declared(&message.response, declared, namespace);
+    out.insert(
+        String::from("response"),
+        schema_json(&response, options, namespace)?,
+    );
+    if !message.errors.is_empty() {
+        let errors = message
+            .errors
+            .iter()
+            .map(|error| {
+                schema_json(
+                    &reference_declared(error, declared, namespace),
+                    options,
+                    namespace,
+                )
+            })
+            .collect::<Result<Vec<Value>, serde_json::Error>>()?;
+        out.insert(String::from("errors"), Value::Array(errors));
+    }
+    if message.one_way {
+        out.insert(String::from("one-way"), Value::Bool(true));
+    }
+    Ok(Value::Object(out))
+}
+
+// `apache_avro` doesn't write the order of the fields, the ones that aren't
+// ascending are added to the JSON of `schema`, and the float defaults of
+// `options` are written as integers
+fn schema_json(
+    schema: &Schema,
+    options: &WriteOptions,
+    enclosing_namespace: &Namespace,
+) -> Result<Value, serde_json::Error> {
+    let mut value = serde_json::to_value(schema)?;
+    add_field_attributes(schema, &mut value, options, enclosing_namespace);
+    Ok(value)
+}
+
+fn add_field_attributes(
+    schema: &Schema,
+    value: &mut Value,
+    options: &WriteOptions,
+    enclosing_namespace: &Namespace,
+) {
+    match schema {
+        Schema::Record(RecordSchema { name, fields, .. }) => {
+            let name = name.fully_qualified_name(enclosing_namespace);
+            for (field, field_value) in fields
+                .iter()
+                .zip(value["fields"].as_array_mut().into_iter().flatten())
+            {
+                match field.order {
+                    RecordFieldOrder::Ascending => {}
+                    RecordFieldOrder::Descending => {
+                        field_value["order"] = Value::from("descending")
+                    }
+                    RecordFieldOrder::Ignore => field_value["order"] = Value::from("ignore"),
+                }
+                if let Some(default) = &field.default {
+                    if options.is_integral_default(&name, &field.name) {
+                        field_value["default"] = integral_default(default);
+                    }
+                }
+                add_field_attributes(
+                    &field.schema,
+                    &mut field_value["type"],
+                    options,
+                    &name.namespace,
+                );
+            }
+        }
+        Schema::Array(items) => {
+            add_field_attributes(items, &mut value["items"], options, enclosing_namespace)
+        }
+        Schema::Map(values) => {
+            add_field_attributes(values, &mut value["values"], options, enclosing_namespace)
+        }
+        Schema::Union(union_schema) => {
+            for (variant, variant_value) in union_schema
+                .variants()
+                .iter()
+                .zip(value.as_array_mut().into_iter().flatten())
+            {
+                add_field_attributes(variant, variant_value, options, enclosing_namespace);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{parse, parse_idl};
+
+    #[test]
+    fn test_to_avsc() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            enum Status { ACTIVE, INACTIVE }
+            record Customer {
+                Status status;
+                union { null, Status } previous = null;
+            }
+            record Order {
+                Customer customer;
+                array<Customer> others;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+        let out: Value = serde_json::from_str(&to_avsc(&schemas).unwrap()).unwrap();
+
+        let expected = json!([
+            {
+                "type": "enum",
+                "name": "Status",
+                "namespace": "org.example",
+                "symbols": ["ACTIVE", "INACTIVE"]
+            },
+            {
+                "type": "record",
+                "name": "Customer",
+                "namespace": "org.example",
+                "fields": [
+                    { "name": "status", "type": "org.example.Status" },
+                    { "name": "previous", "type": ["null", "org.example.Status"], "default": null }
+                ]
+            },
+            {
+                "type": "record",
+                "name": "Order",
+                "namespace": "org.example",
+                "fields": [
+                    { "name": "customer", "type": "org.example.Customer" },
+                    { "name": "others", "type": { "type": "array", "items": "org.example.Customer" } }
+                ]
+            }
+        ]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_to_avsc_field_order() {
+        let input = r#"protocol Market {
+            record Stock {
+                string @order("ascending") name;
+                long @order("descending") price;
+                int @order("ignore") count;
+                int total;
+                union { null, Stock } previous = null;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+        let out: Value = serde_json::from_str(&to_avsc(&schemas).unwrap()).unwrap();
+
+        let orders: Vec<Option<&Value>> = out["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field.get("order"))
+            .collect();
+        assert_eq!(
+            orders,
+            vec![
+                None,
+                Some(&Value::from("descending")),
+                Some(&Value::from("ignore")),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_avpr() {
+        let input = r#"/** Says hello */
+        @namespace("org.example")
+        protocol Greeter {
+            record Greeting { string message; }
+            error Failure { string reason; }
+            Greeting hello(string name = "world") throws Failure;
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+        let out: Value = serde_json::from_str(&to_avpr(&protocol).unwrap()).unwrap();
+
+        let expected = json!({
+            "protocol": "Greeter",
+            "namespace": "org.example",
+            "doc": "Says hello",
+            "types": [
+                {
+                    "type": "record",
+                    "name": "Greeting",
+                    "namespace": "org.example",
+                    "fields": [{ "name": "message", "type": "string" }]
+                },
+                {
+                    "type": "error",
+                    "name": "Failure",
+                    "namespace": "org.example",
+                    "fields": [{ "name": "reason", "type": "string" }]
+                }
+            ],
+            "messages": {
+                "hello": {
+                    "request": [{ "name": "name", "type": "string", "default": "world" }],
+                    "response": "org.example.Greeting",
+                    "errors": ["org.example.Failure"]
+                }
+            }
+        });
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_to_avpr_param_aliases() {
+        let input = r#"protocol Greeter {
+            string hello(string @aliases(["old", "older"]) name, int count = 1);
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+        let request = &protocol.messages[0].request;
+        assert_eq!(
+            request[0].aliases,
+            Some(vec![String::from("old"), String::from("older")])
+        );
+        assert_eq!(request[1].aliases, None);
+
+        let out: Value = serde_json::from_str(&to_avpr(&protocol).unwrap()).unwrap();
+        assert_eq!(
+            out["messages"]["hello"]["request"],
+            json!([
+                { "name": "name", "type": "string", "aliases": ["old", "older"] },
+                { "name": "count", "type": "int", "default": 1 }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_integral_defaults() {
+        let input = r#"@namespace("org.example")
+        protocol Market {
+            record Stock {
+                double price = 5;
+                array<float> @order("ignore") history = [1];
+                union { double, null } ratio = 2;
+                double change = 0.5;
+            }
+            void buy(Stock stock, double amount = 10, float fee = 1.0);
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+        let defaults = |stock: &Value| -> Vec<Value> {
+            stock["fields"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|field| field["default"].clone())
+                .collect()
+        };
+
+        let out: Value = serde_json::from_str(&to_avsc(&protocol.types).unwrap()).unwrap();
+        assert_eq!(
+            defaults(&out),
+            vec![json!(5.0), json!([1.0]), json!(2.0), json!(0.5)]
+        );
+
+        let out = to_avsc_with_options(&protocol.types, &protocol.write_options()).unwrap();
+        let out: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(
+            defaults(&out),
+            vec![json!(5), json!([1.0]), json!(2), json!(0.5)]
+        );
+        assert_eq!(out["fields"][1]["order"], json!("ignore"));
+
+        let out: Value = serde_json::from_str(&to_avpr(&protocol).unwrap()).unwrap();
+        assert_eq!(
+            defaults(&out["types"][0]),
+            vec![json!(5), json!([1.0]), json!(2), json!(0.5)]
+        );
+        let request = &out["messages"]["buy"]["request"];
+        assert_eq!(request[1]["default"], json!(10));
+        assert_eq!(request[2]["default"], json!(1.0));
+    }
+}
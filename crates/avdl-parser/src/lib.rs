@@ -1,3 +1,15 @@
 pub mod string_parser;
 pub mod parser;
-pub use parser::parse;
+pub mod proto;
+pub mod avdl;
+pub mod json;
+pub mod protocol;
+pub mod diagnostics;
+pub mod references;
+pub use parser::{
+    parse, parse_bytes, parse_file, parse_idl, parse_idl_with_options, parse_protocols,
+    parse_reader, parse_schemas, validate, validate_references, validate_schema, AvdlError,
+    ParseError, ParseErrorKind, ParseOptions, TypeKind,
+};
+pub use protocol::{Message, Protocol, WriteOptions};
+pub use references::schema_references;
@@ -1,8 +1,11 @@
 use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 use thiserror::Error;
 
+use crate::protocol::{Message, Protocol};
 use crate::string_parser::parse_string as parse_string_uni;
 use apache_avro::schema::{
     Alias, EnumSchema, FixedSchema, Name, Namespace, RecordFieldOrder, RecordSchema,
@@ -14,19 +17,20 @@ use nom::character::complete::space0;
 
 use nom::combinator::verify;
 
+use nom::error::{ErrorKind, FromExternalError};
 use nom::multi::separated_list0;
-use nom::sequence::pair;
+use nom::sequence::{pair, separated_pair};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_until, take_while, take_while1},
-    character::complete::{char, digit1, multispace0},
-    combinator::{cut, map, map_res, opt, value},
-    multi::{many1, separated_list1},
+    character::complete::{char, digit0, digit1, multispace0, multispace1, one_of},
+    combinator::{consumed, cut, eof, fail, map, map_res, not, opt, recognize, value},
+    multi::{many0, many1, separated_list1},
     sequence::{delimited, preceded, terminated, tuple},
-    AsChar, IResult, InputTake, InputTakeAtPosition, Parser,
+    AsChar, InputTake, InputTakeAtPosition, Offset, Parser,
 };
 use nom_permutation::permutation_opt;
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::str::FromStr;
 use uuid::Uuid;
 
@@ -34,10 +38,120 @@ use uuid::Uuid;
 type VarName<'a> = &'a str;
 type EnumSymbol<'a> = &'a str;
 type Doc = String;
+// A field as declared: its type, doc, order, aliases, name and default
+type FieldDeclaration<'a> = (
+    Schema,
+    Option<Doc>,
+    Option<RecordFieldOrder>,
+    Option<Vec<String>>,
+    VarName<'a>,
+    Option<Value>,
+);
+
+// All the parsers share the same error, which can carry a message on top of
+// the position where the parser gave up.
+type IResult<I, O, E = InputError<I>> = nom::IResult<I, O, E>;
+
+// Where a record field or a message parameter is written, slices of the source
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FieldSource<'a> {
+    // Up to the name, annotations included
+    schema: &'a str,
+    name: &'a str,
+    // Starting right after the `=`
+    default: Option<&'a str>,
+}
+
+// Where the names, fields and referenced types of a declaration are written
+#[derive(Debug, Default, PartialEq)]
+struct DeclarationSource<'a> {
+    names: Vec<&'a str>,
+    fields: Vec<FieldSource<'a>>,
+    types: Vec<&'a str>,
+}
+
+// Sources of the declarations of a protocol, errors only found once the
+// protocol is resolved point at them
+#[derive(Debug, Default)]
+struct Sources<'a> {
+    // Every name declared, in order
+    names: Vec<&'a str>,
+    // The fields of the records, by their fullname
+    fields: HashMap<Name, Vec<FieldSource<'a>>>,
+    // Every type of a field, a parameter, a response or a throws clause, in order
+    types: Vec<&'a str>,
+}
+
+/// Error returned by the parsers. `input` is the remaining input at the point of
+/// failure, so its length can be used to locate the error in the original source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputError<I> {
+    pub input: I,
+    pub kind: ErrorKind,
+    pub message: Option<String>,
+    /// Set when the failure is one of the known `ParseErrorKind`s
+    pub reason: Option<ParseErrorKind>,
+}
+
+impl<I> InputError<I> {
+    fn from_reason(input: I, kind: ErrorKind, reason: ParseErrorKind) -> Self {
+        InputError {
+            input,
+            kind,
+            message: Some(reason.to_string()),
+            reason: Some(reason),
+        }
+    }
+}
+
+impl<I> nom::error::ParseError<I> for InputError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        InputError {
+            input,
+            kind,
+            message: None,
+            reason: None,
+        }
+    }
+
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I, E: std::fmt::Display> FromExternalError<I, E> for InputError<I> {
+    fn from_external_error(input: I, kind: ErrorKind, e: E) -> Self {
+        InputError {
+            input,
+            kind,
+            message: Some(e.to_string()),
+            reason: None,
+        }
+    }
+}
+
+// Like `map_res`, but a rejected value is a `Failure` pointing at the start of the
+// input, so `many1`/`alt` can't backtrack over it and hide the real reason.
+fn map_res_cut<'a, O1, O2, E2: Into<ParseErrorKind>>(
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, O1>,
+    mut f: impl FnMut(O1) -> Result<O2, E2>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O2> {
+    move |input: &'a str| {
+        let (tail, value) = parser(input)?;
+        match f(value) {
+            Ok(value) => Ok((tail, value)),
+            Err(e) => Err(nom::Err::Failure(InputError::from_reason(
+                input,
+                ErrorKind::MapRes,
+                e.into(),
+            ))),
+        }
+    }
+}
 
 // Sample:
 // `/* Hello */`
-// `// Hello\n`
+// `// Hello\n`, or up to the end of the input
 fn parse_comment<'a, T, E>(input: T) -> IResult<T, T, E>
 where
     E: nom::error::ParseError<T>,
@@ -54,7 +168,7 @@ where
 {
     alt((
         delimited(tag("/*"), take_until("*/"), tag("*/")),
-        delimited(tag("//"), take_till(|c| c == '\n'), tag("\n")),
+        delimited(tag("//"), take_till(|c| c == '\n'), alt((tag("\n"), eof))),
     ))(input)
 }
 
@@ -99,11 +213,64 @@ where
 // /** This is a doc */
 // ```
 fn parse_doc(input: &str) -> IResult<&str, Doc> {
-    delimited(
-        tag("/**"),
-        map(take_until("*/"), |v: &str| String::from(v.trim())),
-        tag("*/"),
-    )(input)
+    delimited(tag("/**"), map(take_until("*/"), normalize_doc), tag("*/"))(input)
+}
+
+// The doc comment of a protocol declaration, other comments may follow it
+// ```
+// /** A record */ // TODO: more fields
+// record Simple { string name; }
+// ```
+fn parse_declaration_doc(input: &str) -> IResult<&str, Option<Doc>> {
+    opt(terminated(parse_doc, blank))(input)
+}
+
+// Strip the margin of the lines of the doc block: their leading `*` when every
+// line after the first has one, their common indentation otherwise. Trailing
+// whitespace is trimmed and paragraphs are kept, separated by one blank line.
+// ```
+// /**
+//  * First paragraph.
+//  *
+//  * Second paragraph.
+//  */
+// ```
+fn normalize_doc(doc: &str) -> Doc {
+    let mut lines = doc.lines().map(str::trim_end);
+    let first = lines.next().unwrap_or_default().trim_start();
+    let rest: Vec<&str> = lines.collect();
+    let text = rest.iter().filter(|line| !line.trim().is_empty());
+    let starred = text.clone().all(|line| line.trim_start().starts_with('*'));
+    let indent = text
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).count())
+        .min()
+        .unwrap_or_default();
+    let rest = rest.iter().map(|line| {
+        if starred {
+            let line = line.trim_start();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line)
+        } else {
+            let start = line
+                .char_indices()
+                .nth(indent)
+                .map_or(line.len(), |(i, _)| i);
+            &line[start..]
+        }
+    });
+
+    let mut out: Vec<&str> = Vec::new();
+    for line in std::iter::once(first).chain(rest) {
+        let blank = line.trim().is_empty();
+        if blank && matches!(out.last(), None | Some(&"")) {
+            continue;
+        }
+        out.push(if blank { "" } else { line });
+    }
+    if out.last() == Some(&"") {
+        out.pop();
+    }
+    out.join("\n")
 }
 
 // The name portion of the fullname of named types, record field names, and enum symbols must:
@@ -111,10 +278,17 @@ fn parse_doc(input: &str) -> IResult<&str, Doc> {
 // - start with [A-Za-z_]
 // - subsequently contain only [A-Za-z0-9_]
 // https://avro.apache.org/docs/1.11.1/specification/#names
+//
+// Unicode letters and digits are parsed too, they are rejected once the whole
+// protocol is parsed unless `ParseOptions::unicode_names` is set.
 fn parse_var_name(input: &str) -> IResult<&str, &str> {
     verify(
         take_while(|c| char::is_alphanumeric(c) || c == '_'),
-        |s: &str| s.chars().take(1).any(|c| char::is_alpha(c) || c == '_'),
+        |s: &str| {
+            s.chars()
+                .take(1)
+                .any(|c| char::is_alphabetic(c) || c == '_')
+        },
     )(input)
 }
 
@@ -122,48 +296,63 @@ fn parse_var_name(input: &str) -> IResult<&str, &str> {
 /** Annotations  */
 /** ***********  */
 
+// Any whitespace and comments, as found between the tokens of annotations
+fn blank(input: &str) -> IResult<&str, ()> {
+    value((), many0(alt((multispace1, parse_comment))))(input)
+}
+
+// Whitespace and comments between the declarations of a protocol. A doc comment
+// is left to the declaration it documents.
+fn blank_before_declaration(input: &str) -> IResult<&str, ()> {
+    value(
+        (),
+        many0(alt((multispace1, preceded(not(parse_doc), parse_comment)))),
+    )(input)
+}
+
 // Example:
 // ```
 // @aliases(["name"])
+// @aliases( [ "a", // old
+//             "b" ] )
 // ```
-// TODO: Take into account spaces
 fn parse_aliases(i: &str) -> IResult<&str, Vec<String>> {
     preceded(
         tag("@aliases"),
         delimited(
-            space_or_comment_delimited(tag("(")),
+            pair(blank, tag("(")),
             delimited(
-                tag("["),
-                separated_list1(tag(","), space_or_comment_delimited(parse_namespace_value)),
-                space_or_comment_delimited(tag("]")),
+                pair(blank, tag("[")),
+                separated_list1(tag(","), delimited(blank, parse_namespace_value, blank)),
+                tag("]"),
             ),
-            space_or_comment_delimited(tag(")")),
+            pair(blank, tag(")")),
         ),
     )(i)
 }
 
+// Repeated annotations on a field are merged
+// ```
+// @aliases(["a"]) @aliases(["b"])
+// ```
+fn parse_field_aliases(i: &str) -> IResult<&str, Vec<String>> {
+    map(
+        many1(space_or_comment_delimited(parse_aliases)),
+        |aliases: Vec<Vec<String>>| aliases.concat(),
+    )(i)
+}
+
 // Example:
 // ```
 // @aliases(["org.foo.KindOf"])
 // ```
 fn parse_namespaced_aliases(i: &str) -> IResult<&str, Vec<Alias>> {
-    preceded(
-        tag("@aliases"),
-        delimited(
-            space_or_comment_delimited(tag("(")),
-            delimited(
-                tag("["),
-                separated_list1(
-                    tag(","),
-                    space_or_comment_delimited(map_res(parse_namespace_value, |namespace| {
-                        Alias::new(&namespace)
-                    })),
-                ),
-                space_or_comment_delimited(tag("]")),
-            ),
-            space_or_comment_delimited(tag(")")),
-        ),
-    )(i)
+    map_res(parse_aliases, |aliases| {
+        aliases
+            .iter()
+            .map(|alias| Alias::new(alias))
+            .collect::<Result<Vec<Alias>, _>>()
+    })(i)
 }
 
 // Example:
@@ -188,14 +377,48 @@ fn parse_logical_type(i: &str) -> IResult<&str, Schema> {
     )(i)
 }
 
-// TODO: First and last letter should be alpha only
 fn parse_namespace_value(input: &str) -> IResult<&str, String> {
     let ns = take_while(|c| char::is_alphanumeric(c) || c == '.' || c == '_');
-    map(delimited(char('"'), ns, char('"')), |s: &str| {
-        String::from(s)
+    map_res_cut(delimited(char('"'), ns, char('"')), |s: &str| {
+        validate_namespace(s).map(|()| String::from(s))
     })(input)
 }
 
+// Every dotted part of a namespace follows the Avro name grammar,
+// `[A-Za-z_][A-Za-z0-9_]*`. An empty namespace stands for the null one.
+fn validate_namespace(namespace: &str) -> Result<(), String> {
+    let is_name = |part: &str| {
+        let mut chars = part.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    if namespace.is_empty() || namespace.split('.').all(is_name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid namespace {namespace}, each part must start with a letter or _ and contain only letters, digits and _"
+        ))
+    }
+}
+
+// Enclosing namespace of the types of a schema file
+// Example:
+// ```
+// namespace org.foo;
+// ```
+fn parse_namespace_statement(input: &str) -> IResult<&str, String> {
+    delimited(
+        space_or_comment_delimited(terminated(tag("namespace"), multispace1)),
+        map_res_cut(
+            take_while1(|c| char::is_alphanumeric(c) || c == '.' || c == '_'),
+            |s: &str| validate_namespace(s).map(|()| String::from(s)),
+        ),
+        preceded(blank, tag(";")),
+    )(input)
+}
+
 // Example:
 // ```
 // @namespace("org.foo.KindOf")
@@ -232,6 +455,76 @@ pub fn parse_order(input: &str) -> IResult<&str, RecordFieldOrder> {
     )(input)
 }
 
+// Annotations with a meaning of their own, parsed by their own parsers
+const KNOWN_ANNOTATIONS: [&str; 4] = ["aliases", "logicalType", "namespace", "order"];
+
+// Sample:
+// ```
+// @java-class("java.util.Date")
+// @my.props({"a": [1, true]})
+// ```
+fn parse_custom_annotation(input: &str) -> IResult<&str, (String, Value)> {
+    preceded(
+        char('@'),
+        pair(
+            map(
+                verify(
+                    take_while1(|c: char| c.is_alphanumeric() || "_-.".contains(c)),
+                    |name: &str| !KNOWN_ANNOTATIONS.contains(&name),
+                ),
+                String::from,
+            ),
+            delimited(
+                space_delimited(tag("(")),
+                parse_json_value,
+                preceded(multispace0, tag(")")),
+            ),
+        ),
+    )(input)
+}
+
+// Custom annotations of a declaration, by name
+fn parse_custom_annotations(input: &str) -> IResult<&str, BTreeMap<String, Value>> {
+    map(
+        many0(space_or_comment_delimited(parse_custom_annotation)),
+        BTreeMap::from_iter,
+    )(input)
+}
+
+// Any JSON value, as given to annotations
+fn parse_json_value(input: &str) -> IResult<&str, Value> {
+    space_delimited(alt((
+        map(parse_string_uni, Value::String),
+        value(Value::Null, tag("null")),
+        value(Value::Bool(true), tag("true")),
+        value(Value::Bool(false), tag("false")),
+        map_res(float_literal, serde_json::from_str::<Value>),
+        map(
+            delimited(
+                tag("["),
+                separated_list0(tag(","), parse_json_value),
+                preceded(multispace0, tag("]")),
+            ),
+            Value::Array,
+        ),
+        map(
+            delimited(
+                tag("{"),
+                separated_list0(
+                    tag(","),
+                    separated_pair(
+                        space_delimited(parse_string_uni),
+                        tag(":"),
+                        parse_json_value,
+                    ),
+                ),
+                preceded(multispace0, tag("}")),
+            ),
+            |entries| Value::Object(entries.into_iter().collect()),
+        ),
+    )))(input)
+}
+
 /** ***************************** */
 /** Map Native and Logical Types  */
 /** ***************************** */
@@ -251,20 +544,169 @@ fn map_uuid(input: &str) -> IResult<&str, AvroValue> {
     })(input)
 }
 
-fn map_bytes(input: &str) -> IResult<&str, AvroValue> {
-    map(parse_string_uni, |v| {
-        let v: Vec<u8> = Vec::from(v);
-        AvroValue::Bytes(v)
+// Sample:
+// ```
+// "12:34:56.789"
+// ```
+// Turned into milliseconds since midnight, only allowed in lenient mode
+fn map_time_of_day(input: &str) -> IResult<&str, AvroValue> {
+    map_res(parse_string_uni, |v| -> Result<AvroValue, String> {
+        Ok(AvroValue::Int(time_of_day_millis(&v)?))
     })(input)
 }
 
-fn map_decimal(input: &str) -> IResult<&str, AvroValue> {
-    map(parse_string_uni, |v| {
-        let v: Vec<u8> = Vec::from(v);
-        AvroValue::Decimal(v.into())
+// `HH:MM:SS` with an optional fraction of up to 3 digits
+fn time_of_day_millis(time: &str) -> Result<i32, String> {
+    let invalid = || format!("{time} is not a valid time of day");
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    let (hms, millis) = match time.split_once('.') {
+        Some((hms, fraction)) if (1..=3).contains(&fraction.len()) && is_digits(fraction) => (
+            hms,
+            format!("{fraction:0<3}")
+                .parse::<i32>()
+                .map_err(|_| invalid())?,
+        ),
+        Some(_) => return Err(invalid()),
+        None => (time, 0),
+    };
+    let parts = hms
+        .split(':')
+        .map(|p| {
+            if p.len() == 2 && is_digits(p) {
+                p.parse::<i32>().ok()
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<i32>>>()
+        .ok_or_else(invalid)?;
+    match parts[..] {
+        [h, m, s] if h < 24 && m < 60 && s < 60 => Ok(((h * 60 + m) * 60 + s) * 1000 + millis),
+        _ => Err(invalid()),
+    }
+}
+
+// Sample:
+// ```
+// "\u00ff\u0000"
+// ```
+fn map_bytes(input: &str) -> IResult<&str, AvroValue> {
+    map_res(parse_string_uni, |v| latin1_bytes(&v).map(AvroValue::Bytes))(input)
+}
+
+// Avro writes bytes as a JSON string, a code point from `\u0000` to `\u00ff`
+// for each byte
+pub(crate) fn latin1_bytes(value: &str) -> Result<Vec<u8>, String> {
+    value
+        .chars()
+        .map(|c| {
+            u8::try_from(c).map_err(|_| format!("{c} is not a byte, only \\u0000 to \\u00ff are"))
+        })
+        .collect()
+}
+
+fn latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+// Sample:
+// ```
+// "abcd"
+// ```
+// The default of a fixed must be a string of exactly its size in bytes.
+fn map_fixed(input: &str, size: usize) -> IResult<&str, AvroValue> {
+    map_res_cut(parse_string_uni, |v| -> Result<AvroValue, String> {
+        let bytes = latin1_bytes(&v)?;
+        check_fixed_size(bytes.len(), size)?;
+        Ok(AvroValue::Fixed(size, bytes))
     })(input)
 }
 
+fn check_fixed_size(len: usize, size: usize) -> Result<(), String> {
+    if len == size {
+        Ok(())
+    } else {
+        Err(format!(
+            "A fixed of size {size} takes a default of {size} bytes, found {len}"
+        ))
+    }
+}
+
+// Samples:
+// ```
+// "12.34"
+// 12.34
+// ```
+// Avro stores decimals as the big-endian two's-complement bytes of the
+// unscaled value, so with a scale of 2 the samples become `1234`.
+fn map_decimal(input: &str, precision: usize, scale: usize) -> IResult<&str, AvroValue> {
+    map_res_cut(
+        alt((parse_string_uni, map(float_literal, String::from))),
+        |v| -> Result<AvroValue, String> {
+            let unscaled = unscaled_decimal(&v, precision, scale)?;
+            Ok(AvroValue::Decimal(decimal_bytes(unscaled).into()))
+        },
+    )(input)
+}
+
+fn unscaled_decimal(value: &str, precision: usize, scale: usize) -> Result<i128, String> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let all_digits = integer
+        .chars()
+        .chain(fraction.chars())
+        .all(|c| c.is_ascii_digit());
+    if (integer.is_empty() && fraction.is_empty()) || !all_digits {
+        return Err(format!("{value} is not a valid decimal"));
+    }
+    if fraction.len() > scale {
+        return Err(format!("{value} has more than {scale} fractional digits"));
+    }
+    let unscaled = format!("{integer}{fraction:0<scale$}")
+        .parse::<i128>()
+        .map_err(|_| format!("{value} is too big for a decimal"))?;
+    if unscaled.to_string().len() > precision {
+        return Err(format!("{value} has more than {precision} digits"));
+    }
+    Ok(if negative { -unscaled } else { unscaled })
+}
+
+// Shortest big-endian two's-complement representation of `unscaled`
+fn decimal_bytes(unscaled: i128) -> Vec<u8> {
+    let bytes = unscaled.to_be_bytes();
+    let mut start = 0;
+    while start < bytes.len() - 1 {
+        let sign_extension = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0);
+        if !sign_extension {
+            break;
+        }
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+// The number held by the big-endian two's-complement `bytes` of a decimal,
+// with `scale` fractional digits
+pub(crate) fn decimal_text(bytes: &[u8], scale: usize) -> String {
+    let first = bytes.first().map_or(0, |b| *b as i8 as i128);
+    let unscaled = bytes
+        .iter()
+        .skip(1)
+        .fold(first, |acc, b| (acc << 8) | *b as i128);
+    let digits = format!("{:0>width$}", unscaled.unsigned_abs(), width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    let sign = if unscaled < 0 { "-" } else { "" };
+    if scale == 0 {
+        format!("{sign}{integer}")
+    } else {
+        format!("{sign}{integer}.{fraction}")
+    }
+}
+
 // Sample
 // ```
 // null
@@ -303,25 +745,37 @@ fn map_long(input: &str) -> IResult<&str, AvroValue> {
     })(input)
 }
 
+// Samples:
+// ```
+// 20.0
+// .5
+// -1.5e-3
+// ```
+fn float_literal(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        opt(one_of("+-")),
+        alt((
+            recognize(pair(digit1, opt(pair(char('.'), digit0)))),
+            recognize(pair(char('.'), digit1)),
+        )),
+        opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+    )))(input)
+}
+
 // Sample:
 // ```
 // 20.0
 // ```
 fn map_float(input: &str) -> IResult<&str, AvroValue> {
     map(
-        map_res(
-            take_while1(|c| char::is_digit(c, 10) || c == '.' || c == 'e'),
-            |v: &str| {
-                // Hack to properly deal with float + avro
-                let val = v.parse::<f32>().map_err(|e| e.to_string())?;
-                if val.is_infinite() {
-                    return Err("Invalid float".to_string());
-                }
-
-                v.parse::<f64>().map_err(|e| e.to_string())
-            },
-        ),
-        |v| AvroValue::Double(v),
+        map_res(float_literal, |v: &str| {
+            let val = v.parse::<f32>().map_err(|e| e.to_string())?;
+            if val.is_infinite() {
+                return Err("Invalid float".to_string());
+            }
+            Ok(val)
+        }),
+        AvroValue::Float,
     )(input)
 }
 
@@ -330,13 +784,9 @@ fn map_float(input: &str) -> IResult<&str, AvroValue> {
 // 20.0
 // ```
 fn map_double(input: &str) -> IResult<&str, AvroValue> {
-    map(
-        map_res(
-            take_while1(|c| char::is_digit(c, 10) || c == '.' || c == 'e'),
-            |v: &str| v.parse::<f64>(),
-        ),
-        |v| AvroValue::Double(v),
-    )(input)
+    map(map_res(float_literal, |v: &str| v.parse::<f64>()), |v| {
+        AvroValue::Double(v)
+    })(input)
 }
 
 // Used to parse decimal information
@@ -347,28 +797,25 @@ fn map_usize(input: &str) -> IResult<&str, usize> {
 // Identify correct Schema
 fn map_type_to_schema(input: &str) -> IResult<&str, Schema> {
     alt((
-        preceded(
-            tag("array"),
-            delimited(
-                tag("<"),
-                map(map_type_to_schema, |s| Schema::Array(Box::new(s))),
-                tag(">"),
-            ),
-        ),
-        map(
+        map(preceded(tag("array"), parse_type_parameter), |s| {
+            Schema::Array(Box::new(s))
+        }),
+        map(preceded(tag("map"), parse_type_parameter), |s| {
+            Schema::Map(Box::new(s))
+        }),
+        map_res_cut(
             preceded(
                 space_or_comment_delimited(tag("union")),
                 delimited(
-                    space_delimited(tag("{")),
-                    separated_list1(space_delimited(tag(",")), map_type_to_schema),
-                    space_delimited(tag("}")),
+                    space_or_comment_delimited(tag("{")),
+                    separated_list0(
+                        space_or_comment_delimited(tag(",")),
+                        preceded(blank, map_type_to_schema),
+                    ),
+                    preceded(blank, tag("}")),
                 ),
             ),
-            |union_schemas| {
-                Schema::Union(
-                    UnionSchema::new(union_schemas).expect("Failed to create union schema"),
-                )
-            },
+            union_to_schema,
         ),
         value(Schema::Null, space_or_comment_delimited(tag("null"))),
         value(Schema::Boolean, space_or_comment_delimited(tag("boolean"))),
@@ -386,36 +833,108 @@ fn map_type_to_schema(input: &str) -> IResult<&str, Schema> {
             Schema::TimestampMillis,
             space_or_comment_delimited(tag("timestamp_ms")),
         ),
+        value(
+            Schema::TimeMicros,
+            space_or_comment_delimited(tag("time_us")),
+        ),
+        value(
+            Schema::TimestampMicros,
+            space_or_comment_delimited(tag("timestamp_us")),
+        ),
         value(Schema::Date, space_or_comment_delimited(tag("date"))),
         value(Schema::Uuid, space_or_comment_delimited(tag("uuid"))),
-        map(
+        map_res_cut(
             preceded(
                 space_or_comment_delimited(tag("decimal")),
                 delimited(
                     tag("("),
-                    pair(terminated(map_usize, space_delimited(tag(","))), map_usize),
+                    pair(
+                        terminated(space_delimited(map_usize), tag(",")),
+                        space_delimited(map_usize),
+                    ),
                     tag(")"),
                 ),
             ),
             |(precision, scale)| {
+                if scale > precision {
+                    return Err(format!(
+                        "Decimal scale {scale} is greater than its precision {precision}"
+                    ));
+                }
                 // TODO: Review If inner should be float or calculated differently
-                Schema::Decimal(DecimalSchema {
+                Ok(Schema::Decimal(DecimalSchema {
                     precision: precision,
                     scale: scale,
                     inner: Box::new(Schema::Bytes),
-                })
-            },
-        ),
-        map_res(
-            space_or_comment_delimited(parse_var_name),
-            |reference_name| -> Result<Schema, String> {
-                let name = Name::new(reference_name).map_err(|_e| "Invalid reference name")?;
-                Ok(Schema::Ref { name })
+                }))
             },
         ),
+        parse_reference,
     ))(input)
 }
 
+// The type of the items of an array or the values of a map, whitespace and
+// comments are allowed around it
+// ```
+// < int >
+// ```
+fn parse_type_parameter(input: &str) -> IResult<&str, Schema> {
+    delimited(
+        pair(blank, tag("<")),
+        preceded(blank, map_type_to_schema),
+        pair(blank, tag(">")),
+    )(input)
+}
+
+// A type can only appear once in a union, named types are told apart by their name
+fn union_to_schema(variants: Vec<Schema>) -> Result<Schema, String> {
+    if variants.is_empty() {
+        return Err(String::from("A union needs at least one type"));
+    }
+    for (i, variant) in variants.iter().enumerate() {
+        if variants[..i].contains(variant) {
+            let type_name = match variant {
+                Schema::Record(RecordSchema { name, .. })
+                | Schema::Enum(EnumSchema { name, .. })
+                | Schema::Fixed(FixedSchema { name, .. })
+                | Schema::Ref { name } => name.fullname(None),
+                other => serde_json::to_string(other)
+                    .unwrap_or_default()
+                    .trim_matches('"')
+                    .to_string(),
+            };
+            return Err(format!("Duplicate type {type_name} in union"));
+        }
+    }
+    let union_schema = UnionSchema::new(variants).map_err(|e| e.to_string())?;
+    Ok(Schema::Union(union_schema))
+}
+
+// Reference to a named type, by its name or fullname
+fn parse_reference(input: &str) -> IResult<&str, Schema> {
+    map(
+        space_or_comment_delimited(recognize(separated_list1(char('.'), parse_var_name))),
+        |reference_name| Schema::Ref {
+            name: parsed_name(reference_name),
+        },
+    )(input)
+}
+
+// `Name::new` only accepts ASCII names, the parsed ones are checked once the
+// whole protocol is parsed, see `find_non_ascii_name`.
+fn parsed_name(fullname: &str) -> Name {
+    match fullname.rsplit_once('.') {
+        Some((namespace, name)) => Name {
+            name: name.to_string(),
+            namespace: Some(namespace.to_string()),
+        },
+        None => Name {
+            name: fullname.to_string(),
+            namespace: None,
+        },
+    }
+}
+
 // Identify default parser based on the given Schema
 fn parse_based_on_schema<'r>(
     schema: Box<Schema>,
@@ -431,38 +950,62 @@ fn parse_based_on_schema<'r>(
         Schema::String => Box::new(map_string),
         Schema::Array(schema) => Box::new(move |input: &'r str| {
             delimited(
-                tag("["),
+                space_delimited(tag("[")),
                 map(
-                    separated_list0(tag(","), parse_based_on_schema(schema.clone())),
+                    separated_list0(
+                        space_delimited(tag(",")),
+                        parse_based_on_schema(schema.clone()),
+                    ),
                     |s| AvroValue::Array(s),
                 ),
-                tag("]"),
+                preceded(multispace0, tag("]")),
             )(input)
         })
             as Box<dyn FnMut(&'r str) -> IResult<&'r str, AvroValue> + '_>,
-        Schema::Union(union_schema) => {
-            let schema = union_schema
-                .variants()
-                .first()
-                .expect("There should be at least 2 schemas in the union");
-
-            parse_based_on_schema(Box::new(schema.clone()))
-        }
+        Schema::Map(schema) => Box::new(move |input: &'r str| {
+            delimited(
+                space_delimited(tag("{")),
+                map(
+                    separated_list0(
+                        space_delimited(tag(",")),
+                        pair(
+                            parse_string_uni,
+                            preceded(
+                                space_delimited(tag(":")),
+                                parse_based_on_schema(schema.clone()),
+                            ),
+                        ),
+                    ),
+                    |v| AvroValue::Map(HashMap::from_iter(v)),
+                ),
+                preceded(multispace0, tag("}")),
+            )(input)
+        }),
+        // Avro takes the default of a union as a value of its first type
+        Schema::Union(union_schema) => match union_schema.variants().first() {
+            Some(first) => parse_based_on_schema(Box::new(first.clone())),
+            None => Box::new(|input: &'r str| fail(input)),
+        },
 
         // Logical Types
         Schema::Date => Box::new(map_int),
-        Schema::TimeMillis => Box::new(map_int),
+        Schema::TimeMillis => Box::new(|input: &'r str| alt((map_int, map_time_of_day))(input)),
         Schema::TimestampMillis => Box::new(map_long),
         Schema::Uuid => Box::new(map_uuid),
         Schema::Decimal(DecimalSchema {
-            precision: _,
-            scale: _,
-            inner: _,
-        }) => Box::new(map_decimal),
+            precision, scale, ..
+        }) => Box::new(move |input: &'r str| map_decimal(input, precision, scale)),
         Schema::TimestampMicros => Box::new(map_long),
         Schema::TimeMicros => Box::new(map_long),
         Schema::Duration => todo!("This should be fixed"),
-        Schema::Ref { name: _ } => Box::new(parse_enum_default_symbol),
+        Schema::Fixed(FixedSchema { size, .. }) => {
+            Box::new(move |input: &'r str| map_fixed(input, size))
+        }
+        // An enum symbol, bare or as a JSON string, or the string of a fixed,
+        // checked once the reference is resolved
+        Schema::Ref { name: _ } => {
+            Box::new(|input: &'r str| alt((parse_enum_default_symbol, map_string))(input))
+        }
 
         _ => unimplemented!("Not implemented yet"),
     }
@@ -475,20 +1018,36 @@ fn parse_based_on_schema<'r>(
 // float age = 20;
 // double age = 20.0;
 // ```
-fn parse_field(
-    input: &str,
-) -> IResult<
-    &str,
-    (
-        Schema,
-        Option<Doc>,
-        Option<RecordFieldOrder>,
-        Option<Vec<String>>,
-        VarName,
-        Option<Value>,
-    ),
-> {
-    let (tail, doc) = opt(parse_doc)(input)?;
+fn parse_field(input: &str) -> IResult<&str, FieldDeclaration<'_>> {
+    let (tail, field) = parse_field_declaration(input)?;
+    // Annotations go between the type and the name, e.g. `int @order("ignore") x = 1;`
+    if tail.trim_start().starts_with('@') {
+        let name = field.4;
+        return Err(nom::Err::Failure(InputError::from_external_error(
+            tail.trim_start(),
+            ErrorKind::Verify,
+            format!("Annotations of field {name} must come between its type and its name"),
+        )));
+    }
+    let (tail, _) = preceded(space0, space_or_comment_delimited(tag(";")))(tail).map_err(
+        |_: nom::Err<InputError<&str>>| {
+            let name = field.4;
+            // Point right after the declaration, not at the line that follows it
+            let declaration = input[..input.len() - tail.len()].trim_end();
+            nom::Err::Failure(InputError::from_external_error(
+                &input[declaration.len()..],
+                ErrorKind::Tag,
+                format!("Expected ; after field {name}"),
+            ))
+        },
+    )?;
+    Ok((tail, field))
+}
+
+// A field without the trailing `;`, shared by record fields and message
+// parameters.
+fn parse_field_declaration(input: &str) -> IResult<&str, FieldDeclaration<'_>> {
+    let (tail, doc) = opt(parse_doc)(input)?;
     let (tail, logical_schema) = opt(space_or_comment_delimited(parse_logical_type))(tail)?;
     let (tail, schema) = map_type_to_schema(tail)?;
 
@@ -499,27 +1058,206 @@ fn parse_field(
 
     let boxed_schema = Box::new(schema.clone());
     // let default_parser = ;
-    let (tail, ((order, aliases), varname, defaults)) = terminated(
-        tuple((
-            permutation_opt((
-                space_or_comment_delimited(parse_order),
-                space_or_comment_delimited(parse_aliases),
-            )),
-            space_or_comment_delimited(parse_var_name),
-            // default
-            opt(preceded(
-                space_or_comment_delimited(tag("=")),
-                map_res(parse_based_on_schema(boxed_schema), |value| {
-                    value.try_into()
-                }),
-            )),
+    let (tail, ((order, aliases), varname, defaults)) = tuple((
+        permutation_opt((
+            space_or_comment_delimited(parse_order),
+            space_or_comment_delimited(parse_field_aliases),
         )),
-        preceded(space0, space_or_comment_delimited(tag(";"))),
-    )(tail)?;
+        space_or_comment_delimited(parse_var_name),
+        // default
+        opt(preceded(
+            space_or_comment_delimited(tag("=")),
+            map_res(
+                consumed(parse_based_on_schema(boxed_schema)),
+                |(literal, value)| default_to_json(&schema, literal, value),
+            ),
+        )),
+    ))(tail)?;
+    check_integral_default(&schema, varname, tail)?;
+    if defaults.is_none() {
+        check_default_type(&schema, varname, tail)?;
+    }
 
     Ok((tail, (schema, doc, order, aliases, varname, defaults)))
 }
 
+// `int x = 1.5;` would stop after `1` and fail on the missing `;`, tell what's
+// wrong instead
+fn check_integral_default<'a>(
+    schema: &Schema,
+    varname: &str,
+    tail: &'a str,
+) -> Result<(), nom::Err<InputError<&'a str>>> {
+    let type_name = match schema {
+        Schema::Int => "an int",
+        Schema::Long => "a long",
+        _ => return Ok(()),
+    };
+    if tail.starts_with('.') {
+        return Err(nom::Err::Failure(InputError::from_reason(
+            tail,
+            ErrorKind::Verify,
+            ParseErrorKind::InvalidDefault {
+                field: varname.to_string(),
+                reason: format!("{type_name} can't be fractional"),
+            },
+        )));
+    }
+    Ok(())
+}
+
+// A default that the parser of the field type couldn't read is parsed as any
+// JSON value, to tell why it doesn't fit the field
+fn check_default_type<'a>(
+    schema: &Schema,
+    varname: &str,
+    tail: &'a str,
+) -> Result<(), nom::Err<InputError<&'a str>>> {
+    let Ok((value_input, _)) = space_or_comment_delimited(tag::<_, _, InputError<&str>>("="))(tail)
+    else {
+        return Ok(());
+    };
+    let Ok((_, value)) = parse_json_value(value_input) else {
+        return Ok(());
+    };
+    match validate_default(schema, &value) {
+        Ok(()) => Ok(()),
+        Err(reason) => Err(nom::Err::Failure(InputError::from_reason(
+            value_input.trim_start(),
+            ErrorKind::Verify,
+            ParseErrorKind::InvalidDefault {
+                field: varname.to_string(),
+                reason,
+            },
+        ))),
+    }
+}
+
+// Check that the JSON `value` is a valid default of `schema`, returning the
+// reason it isn't
+fn validate_default(schema: &Schema, value: &Value) -> Result<(), String> {
+    let valid = match (schema, value) {
+        (Schema::Null, Value::Null) | (Schema::Boolean, Value::Bool(_)) => true,
+        (
+            Schema::Int
+            | Schema::Long
+            | Schema::Date
+            | Schema::TimeMillis
+            | Schema::TimeMicros
+            | Schema::TimestampMillis
+            | Schema::TimestampMicros,
+            Value::Number(n),
+        ) => n.is_i64(),
+        (Schema::Float | Schema::Double | Schema::Decimal(_), Value::Number(_)) => true,
+        (
+            Schema::String
+            | Schema::Bytes
+            | Schema::Uuid
+            | Schema::Decimal(_)
+            | Schema::Fixed(_)
+            | Schema::Duration
+            | Schema::Enum(_)
+            | Schema::Ref { .. },
+            Value::String(_),
+        ) => true,
+        (Schema::Array(items), Value::Array(values)) => {
+            return values.iter().try_for_each(|v| validate_default(items, v))
+        }
+        (Schema::Map(values), Value::Object(entries)) => {
+            return entries
+                .values()
+                .try_for_each(|v| validate_default(values, v))
+        }
+        (Schema::Record(_) | Schema::Ref { .. }, Value::Object(_)) => true,
+        (Schema::Union(union_schema), value) => match union_schema.variants().first() {
+            Some(first) => return validate_default(first, value),
+            None => false,
+        },
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected {}, found {value}",
+            type_description(schema)
+        ))
+    }
+}
+
+fn type_description(schema: &Schema) -> String {
+    let description = match schema {
+        Schema::Null => "null",
+        Schema::Boolean => "a boolean",
+        Schema::Int => "an int",
+        Schema::Long => "a long",
+        Schema::Float => "a float",
+        Schema::Double => "a double",
+        Schema::Bytes => "bytes",
+        Schema::String => "a string",
+        Schema::Array(_) => "an array",
+        Schema::Map(_) => "a map",
+        Schema::Union(_) => "one of the types of the union",
+        Schema::Record(_) => "a record",
+        Schema::Enum(_) => "an enum symbol",
+        Schema::Fixed(_) => "a fixed",
+        Schema::Decimal(_) => "a decimal",
+        Schema::Uuid => "a uuid",
+        Schema::Date => "a date",
+        Schema::TimeMillis | Schema::TimeMicros => "a time",
+        Schema::TimestampMillis | Schema::TimestampMicros => "a timestamp",
+        Schema::Duration => "a duration",
+        Schema::Ref { name } => return format!("a {}", name.fullname(None)),
+    };
+    String::from(description)
+}
+
+// Float defaults are written as given.
+fn default_to_json(
+    schema: &Schema,
+    literal: &str,
+    value: AvroValue,
+) -> Result<Value, apache_avro::Error> {
+    if let (Schema::Float, Ok(float)) = (schema, literal.trim().parse::<f64>()) {
+        return Ok(Value::from(float));
+    }
+    value_to_json(value)
+}
+
+// The entries are collected into a JSON object directly, keys are sorted,
+// instead of depending on the hashing of an `AvroValue::Map`.
+fn map_default_to_json(entries: Vec<(String, AvroValue)>) -> Result<Value, apache_avro::Error> {
+    let mut object = Map::new();
+    for (key, value) in entries {
+        object.insert(key, value_to_json(value)?);
+    }
+    Ok(Value::Object(object))
+}
+
+// `AvroValue::Float` would be widened to the closest `f64`, `0.1` becoming
+// `0.10000000149011612`, its shortest decimal form is kept instead. Bytes,
+// fixed and decimals are written as strings, not as arrays of numbers.
+fn value_to_json(value: AvroValue) -> Result<Value, apache_avro::Error> {
+    match value {
+        AvroValue::Float(float) => Ok(Value::from(
+            float.to_string().parse::<f64>().unwrap_or(float.into()),
+        )),
+        AvroValue::Bytes(bytes) | AvroValue::Fixed(_, bytes) => {
+            Ok(Value::String(latin1_string(&bytes)))
+        }
+        AvroValue::Decimal(decimal) => {
+            Vec::<u8>::try_from(decimal).map(|bytes| Value::String(latin1_string(&bytes)))
+        }
+        AvroValue::Array(items) => items
+            .into_iter()
+            .map(value_to_json)
+            .collect::<Result<Vec<Value>, _>>()
+            .map(Value::Array),
+        AvroValue::Map(entries) => map_default_to_json(entries.into_iter().collect()),
+        value => value.try_into(),
+    }
+}
+
 /** ***************  */
 /**  Complex Types  */
 /** *************** */
@@ -545,7 +1283,7 @@ fn parse_array(
     let (tail, doc) = opt(parse_doc)(input)?;
     let (tail, schema_array_type) = preceded(
         space_or_comment_delimited(tag("array")),
-        delimited(tag("<"), map_type_to_schema, tag(">")),
+        parse_type_parameter,
     )(tail)?;
     let schema = Box::new(schema_array_type.clone());
     let array_default_parser = parse_based_on_schema(schema);
@@ -553,20 +1291,20 @@ fn parse_array(
         tuple((
             permutation_opt((
                 space_or_comment_delimited(parse_order),
-                space_or_comment_delimited(parse_aliases),
+                space_or_comment_delimited(parse_field_aliases),
             )),
             space_delimited(parse_var_name),
             // default
             opt(preceded(
                 space_delimited(tag("=")),
                 delimited(
-                    tag("["),
+                    space_delimited(tag("[")),
                     map_res(
-                        separated_list0(tag(","), array_default_parser),
-                        |value| AvroValue::Array(value).try_into(),
+                        separated_list0(space_delimited(tag(",")), array_default_parser),
+                        |value| value_to_json(AvroValue::Array(value)),
                         // Value::Array,
                     ),
-                    tag("]"),
+                    preceded(multispace0, tag("]")),
                 ),
             )),
         )),
@@ -604,17 +1342,15 @@ fn parse_map(
     ),
 > {
     let (tail, doc) = opt(parse_doc)(input)?;
-    let (tail, schema) = preceded(
-        space_or_comment_delimited(tag("map")),
-        delimited(tag("<"), map_type_to_schema, tag(">")),
-    )(tail)?;
+    let (tail, schema) =
+        preceded(space_or_comment_delimited(tag("map")), parse_type_parameter)(tail)?;
     let schema_for_parser = Box::new(schema.clone());
     let map_default_parser = parse_based_on_schema(schema_for_parser);
     let (tail, ((order, aliases), varname, defaults)) = terminated(
         tuple((
             permutation_opt((
                 space_or_comment_delimited(parse_order),
-                space_or_comment_delimited(parse_aliases),
+                space_or_comment_delimited(parse_field_aliases),
             )),
             space_delimited(parse_var_name),
             // default
@@ -630,7 +1366,7 @@ fn parse_map(
                                 preceded(space_delimited(tag(":")), map_default_parser),
                             ),
                         ),
-                        |v| AvroValue::Map(HashMap::from_iter(v)).try_into(),
+                        map_default_to_json,
                     ),
                     tag("}"),
                 ),
@@ -670,21 +1406,37 @@ fn parse_union(
 
     let boxed_schema = Box::new(schema.clone());
     let default_parser = parse_based_on_schema(boxed_schema);
-    let (tail, ((order, aliases), varname, defaults)) = terminated(
-        tuple((
-            permutation_opt((
-                space_or_comment_delimited(parse_order),
-                space_or_comment_delimited(parse_aliases),
-            )),
-            space_or_comment_delimited(parse_var_name),
-            // default
-            opt(preceded(
-                space_or_comment_delimited(tag("=")),
-                map_res(default_parser, |value| value.try_into()),
-            )),
+    let (tail, ((order, aliases), varname, defaults)) = tuple((
+        permutation_opt((
+            space_or_comment_delimited(parse_order),
+            space_or_comment_delimited(parse_field_aliases),
         )),
-        preceded(space0, space_or_comment_delimited(tag(";"))),
-    )(tail)?;
+        space_or_comment_delimited(parse_var_name),
+        // default
+        opt(preceded(
+            space_or_comment_delimited(tag("=")),
+            map_res(consumed(default_parser), |(literal, value)| {
+                default_to_json(&schema, literal, value)
+            }),
+        )),
+    ))(tail)?;
+    check_integral_default(&schema, varname, tail)?;
+    if defaults.is_none() {
+        check_default_type(&schema, varname, tail)?;
+    }
+    // The default of a union is a value of its first type, as required by Avro.
+    // Report it here instead of letting the field backtrack.
+    if matches!(schema, Schema::Union(_)) && defaults.is_none() && tail.starts_with('=') {
+        return Err(nom::Err::Failure(InputError::from_reason(
+            tail,
+            ErrorKind::Verify,
+            ParseErrorKind::InvalidDefault {
+                field: varname.to_string(),
+                reason: String::from("it doesn't match the first of its types"),
+            },
+        )));
+    }
+    let (tail, _) = preceded(space0, space_or_comment_delimited(tag(";")))(tail)?;
 
     Ok((tail, (schema, doc, order, aliases, varname, defaults)))
 }
@@ -703,19 +1455,51 @@ fn parse_enum_item(input: &str) -> IResult<&str, VarName> {
     space_or_comment_delimited(parse_var_name)(input)
 }
 
+// The symbol may be qualified by the enum name, e.g. `org.x.Suit.HEARTS`, it's
+// checked once the enum is resolved.
 fn parse_enum_default_symbol(input: &str) -> IResult<&str, AvroValue> {
-    map(parse_enum_item, |v| AvroValue::String(v.into()))(input)
+    map(
+        space_or_comment_delimited(recognize(separated_list1(char('.'), parse_var_name))),
+        |v: &str| AvroValue::String(v.into()),
+    )(input)
+}
+
+// Sample:
+// ```
+// /** the coin */ COIN
+// ```
+fn parse_documented_enum_item(input: &str) -> IResult<&str, (Option<Doc>, EnumSymbol<'_>)> {
+    pair(opt(space_delimited(parse_doc)), parse_enum_symbol)(input)
+}
+
+// A symbol follows the rules of names, see `parse_var_name`. Anything else up to
+// the next separator is reported as an invalid symbol.
+fn parse_enum_symbol(input: &str) -> IResult<&str, EnumSymbol<'_>> {
+    space_or_comment_delimited(map_res_cut(
+        take_while1(|c: char| !(c.is_whitespace() || c == ',' || c == '}' || c == '/')),
+        validate_enum_symbol,
+    ))(input)
+}
+
+fn validate_enum_symbol(symbol: &str) -> Result<EnumSymbol<'_>, String> {
+    match parse_var_name(symbol) {
+        Ok(("", symbol)) => Ok(symbol),
+        _ => Err(format!(
+            "Invalid enum symbol {symbol}, it must start with a letter or _ and contain only letters, digits and _"
+        )),
+    }
 }
 
 // Sample:
 // ```
-// { COIN, NUMBER }
+// { COIN, /** a number */ NUMBER }
 // ```
-fn parse_enum_symbols(input: &str) -> IResult<&str, Vec<EnumSymbol>> {
+fn parse_enum_symbols(input: &str) -> IResult<&str, Vec<(Option<Doc>, EnumSymbol<'_>)>> {
     delimited(
-        space_or_comment_delimited(tag("{")),
-        separated_list1(tag(","), parse_enum_item),
-        space_or_comment_delimited(tag("}")),
+        // A doc right after the brace belongs to the first symbol
+        delimited(space_delimited(opt(parse_comment)), tag("{"), multispace0),
+        separated_list1(tag(","), parse_documented_enum_item),
+        preceded(blank, tag("}")),
     )(input)
 }
 
@@ -729,42 +1513,81 @@ fn parse_enum_name(input: &str) -> IResult<&str, VarName> {
 
 // Sample:
 // ```
-// = COIN;
+// /* default */ = COIN /* the coin */ ;
 // ```
 fn parse_enum_default(input: &str) -> IResult<&str, String> {
     terminated(
         preceded(
-            space_delimited(tag("=")),
+            pair(blank, tag("=")),
             map(parse_enum_item, |value| value.to_string()),
         ),
-        tag(";"),
+        preceded(blank, tag(";")),
     )(input)
 }
 
 // Sample:
 // ```
 // enum Items { COIN, NUMBER } = COIN;
+// @namespace("org.other") enum Items { COIN, NUMBER }
 // ```
-fn parse_enum(input: &str) -> IResult<&str, Schema> {
-    let (tail, (doc, aliases, name, body, default)) = tuple((
-        opt(parse_doc),
-        opt(parse_namespaced_aliases),
+fn parse_enum_located(input: &str) -> IResult<&str, (Schema, DeclarationSource<'_>)> {
+    let (default_input, (doc, mut attributes, (aliases, namespace), name, body)) = tuple((
+        parse_declaration_doc,
+        parse_custom_annotations,
+        permutation_opt((
+            space_or_comment_delimited(parse_namespaced_aliases),
+            space_or_comment_delimited(parse_namespace),
+        )),
         parse_enum_name,
         parse_enum_symbols,
-        opt(parse_enum_default),
     ))(input)?;
-    let n = Name::new(name).unwrap();
+    let (tail, default) = opt(parse_enum_default)(default_input)?;
+    if let Some(default) = &default {
+        if !body.iter().any(|(_doc, symbol)| symbol == default) {
+            return Err(nom::Err::Failure(InputError::from_reason(
+                default_input.trim_start(),
+                ErrorKind::Verify,
+                ParseErrorKind::InvalidDefault {
+                    field: name.to_string(),
+                    reason: format!("{default} is not one of its symbols"),
+                },
+            )));
+        }
+    }
+    let source = DeclarationSource {
+        names: std::iter::once(name)
+            .chain(body.iter().map(|(_doc, symbol)| *symbol))
+            .collect(),
+        ..Default::default()
+    };
+    let mut n = parsed_name(name);
+    n.namespace = namespace;
+
+    // Avro has no doc on symbols, they are kept as a `symbolDocs` attribute
+    let symbol_docs: serde_json::Map<String, Value> = body
+        .iter()
+        .filter_map(|(doc, symbol)| Some((symbol.to_string(), Value::from(doc.clone()?))))
+        .collect();
+    if !symbol_docs.is_empty() {
+        attributes.insert(String::from("symbolDocs"), Value::Object(symbol_docs));
+    }
 
     Ok((
         tail,
-        Schema::Enum(EnumSchema {
-            name: n,
-            aliases: aliases,
-            doc: doc,
-            symbols: body.into_iter().map(String::from).collect::<Vec<String>>(),
-            attributes: BTreeMap::new(),
-            default: default,
-        }),
+        (
+            Schema::Enum(EnumSchema {
+                name: n,
+                aliases,
+                doc,
+                symbols: body
+                    .into_iter()
+                    .map(|(_doc, symbol)| String::from(symbol))
+                    .collect::<Vec<String>>(),
+                attributes,
+                default,
+            }),
+            source,
+        ),
     ))
 }
 
@@ -772,17 +1595,25 @@ fn parse_enum(input: &str) -> IResult<&str, Schema> {
 // ```
 // fixed MD5(16);
 // fixed @aliases(["md1"]) MD5(16);
+// @java-class("Md5") fixed MD5(16);
+// @namespace("org.other") fixed MD5(16);
 // ```
-fn parse_fixed(input: &str) -> IResult<&str, Schema> {
-    let (tail, (doc, (aliases, name, size))) = tuple((
-        space_delimited(opt(parse_doc)),
+fn parse_fixed_located(input: &str) -> IResult<&str, (Schema, DeclarationSource<'_>)> {
+    let (tail, (doc, attributes, namespace, (aliases, name, size))) = tuple((
+        space_delimited(parse_declaration_doc),
+        parse_custom_annotations,
+        opt(space_or_comment_delimited(parse_namespace)),
         preceded(
             tag("fixed"),
             cut(terminated(
                 space_delimited(tuple((
                     opt(space_delimited(parse_namespaced_aliases)),
                     parse_var_name,
-                    delimited(tag("("), map_usize, tag(")")),
+                    delimited(
+                        pair(blank, tag("(")),
+                        preceded(blank, map_usize),
+                        pair(blank, tag(")")),
+                    ),
                 ))),
                 char(';'),
             )),
@@ -791,23 +1622,33 @@ fn parse_fixed(input: &str) -> IResult<&str, Schema> {
 
     Ok((
         tail,
-        Schema::Fixed(FixedSchema {
-            name: name.into(),
-            aliases: aliases.clone(),
-            doc: doc,
-            size: size,
-            attributes: BTreeMap::new(),
-        }),
+        (
+            Schema::Fixed(FixedSchema {
+                name: Name {
+                    name: name.to_string(),
+                    namespace,
+                },
+                aliases,
+                doc,
+                size,
+                attributes,
+            }),
+            DeclarationSource {
+                names: vec![name],
+                ..Default::default()
+            },
+        ),
     ))
 }
 
 // Sample
 // ```
 // record TestRecord
+// error TestError
 // ```
-fn parse_record_name(input: &str) -> IResult<&str, &str> {
+fn parse_record_name<'a>(input: &'a str, keyword: &str) -> IResult<&'a str, &'a str> {
     preceded(
-        space_or_comment_delimited(tag("record")),
+        space_or_comment_delimited(tag(keyword)),
         space_or_comment_delimited(parse_var_name),
     )(input)
 }
@@ -816,67 +1657,84 @@ fn parse_record_name(input: &str) -> IResult<&str, &str> {
 // This returns a whole schema::RecordField
 // ```
 // string @order("ignore") name = "jon";
+// @java-class("java.util.Date") long ts;
 // ```
-fn parse_record_field(input: &str) -> IResult<&str, RecordField> {
+fn parse_record_field_located(input: &str) -> IResult<&str, (RecordField, FieldSource<'_>)> {
     preceded(
         multispace0,
-        space_or_comment_delimited(alt((
-            map(
-                parse_array,
-                |(schemas, doc, order, aliases, name, default)| RecordField {
-                    name: name.to_string(),
-                    doc: doc,
-                    default: default,
-                    schema: schemas,
-                    order: order.unwrap_or(RecordFieldOrder::Ascending),
-                    aliases: aliases,
-                    position: 0,
-                    custom_attributes: BTreeMap::new(),
-                },
-            ),
-            map(
-                parse_map,
-                |(schemas, doc, order, aliases, name, default)| RecordField {
-                    name: name.to_string(),
-                    doc: doc,
-                    default: default,
-                    schema: schemas,
-                    order: order.unwrap_or(RecordFieldOrder::Ascending),
-                    aliases: aliases,
-                    position: 0,
-                    custom_attributes: BTreeMap::new(),
-                },
-            ),
-            map(
-                parse_union,
-                |(schema, doc, order, aliases, name, default)| RecordField {
-                    name: name.to_string(),
-                    doc: doc,
-                    default: default,
-                    schema: schema,
-                    order: order.unwrap_or(RecordFieldOrder::Ascending),
-                    aliases: aliases,
-                    position: 0,
-                    custom_attributes: BTreeMap::new(),
-                },
-            ),
-            map(
-                parse_field,
-                |(schemas, doc, order, aliases, name, default)| RecordField {
+        space_or_comment_delimited(map(
+            consumed(pair(
+                parse_custom_annotations,
+                alt((parse_array, parse_map, parse_union, parse_field)),
+            )),
+            |(declaration, (annotations, (schema, doc, order, aliases, name, default)))| {
+                let field = RecordField {
                     name: name.to_string(),
-                    doc: doc,
-                    default: default,
-                    schema: schemas,
+                    doc,
+                    default,
+                    schema,
+                    custom_attributes: annotations,
                     order: order.unwrap_or(RecordFieldOrder::Ascending),
-                    aliases: aliases,
+                    aliases,
                     position: 0,
-                    custom_attributes: BTreeMap::new(),
-                },
-            ),
-        ))),
+                };
+                (field, field_source(declaration, name))
+            },
+        )),
     )(input)
 }
 
+// The name is a slice of the declaration, the type precedes it and the default
+// follows it
+fn field_source<'a>(declaration: &'a str, name: &'a str) -> FieldSource<'a> {
+    let after_name = &declaration[declaration.offset(name) + name.len()..];
+    let default = preceded(pair(blank, char('=')), blank)(after_name)
+        .ok()
+        .map(|(default, ())| default);
+    FieldSource {
+        schema: &declaration[..declaration.offset(name)],
+        name,
+        default,
+    }
+}
+
+// Sample of a message parameter
+// ```
+// string @aliases(["old"]) name
+// ```
+fn parse_message_param(input: &str) -> IResult<&str, (RecordField, FieldSource<'_>)> {
+    let (tail, (declaration, (schema, doc, order, aliases, name, default))) =
+        space_or_comment_delimited(consumed(parse_field_declaration))(input)?;
+    // The default is optional, so one not matching the type is left behind
+    if tail.starts_with('=') {
+        return Err(nom::Err::Failure(InputError::from_reason(
+            tail,
+            ErrorKind::Verify,
+            ParseErrorKind::InvalidDefault {
+                field: name.to_string(),
+                reason: String::from("it doesn't match the parameter type"),
+            },
+        )));
+    }
+
+    Ok((
+        tail,
+        (
+            RecordField {
+                name: name.to_string(),
+                doc,
+                default,
+                schema,
+                order: order.unwrap_or(RecordFieldOrder::Ascending),
+                aliases,
+                position: 0,
+                custom_attributes: BTreeMap::new(),
+            },
+            field_source(declaration, name),
+        ),
+    ))
+}
+
 // Sample of record
 // ```
 // record Employee {
@@ -886,54 +1744,308 @@ fn parse_record_field(input: &str) -> IResult<&str, RecordField> {
 // }
 // ```
 pub fn parse_record(input: &str) -> IResult<&str, Schema> {
+    map(
+        |i| parse_record_with_keyword(i, "record"),
+        |(schema, _source)| schema,
+    )(input)
+}
+
+// Sample of error, a record thrown by messages
+// ```
+// error NotFound {
+//     string message;
+// }
+// ```
+pub fn parse_error(input: &str) -> IResult<&str, Schema> {
+    map(
+        |i| parse_record_with_keyword(i, "error"),
+        |(schema, _source)| schema,
+    )(input)
+}
+
+fn parse_record_with_keyword<'a>(
+    input: &'a str,
+    keyword: &str,
+) -> IResult<&'a str, (Schema, DeclarationSource<'a>)> {
     let mut used_field_names = Vec::new();
-    let (tail, (doc, (aliases, namespace), name, fields)) = tuple((
-        opt(parse_doc),
-        permutation_opt((
-            space_or_comment_delimited(parse_namespaced_aliases),
-            space_or_comment_delimited(parse_namespace),
-        )),
-        parse_record_name,
-        preceded(
-            multispace0,
-            delimited(
-                tag("{"),
-                many1(map_res(parse_record_field, |f| {
-                    let name = f.name.clone();
-                    if used_field_names.contains(&name) {
-                        return Err("Duplicate field {name}");
-                    }
-                    used_field_names.push(name);
-                    Ok(f)
-                })),
-                preceded(multispace0, tag("}")),
+    let (tail, (doc, mut attributes, (aliases, namespace), more_attributes, name, fields)) =
+        tuple((
+            parse_declaration_doc,
+            parse_custom_annotations,
+            permutation_opt((
+                space_or_comment_delimited(parse_namespaced_aliases),
+                space_or_comment_delimited(parse_namespace),
+            )),
+            parse_custom_annotations,
+            |i| parse_record_name(i, keyword),
+            preceded(
+                multispace0,
+                delimited(
+                    tag("{"),
+                    many1(preceded(
+                        multispace0,
+                        map_res_cut(parse_record_field_located, |(mut f, source)| {
+                            let name = f.name.clone();
+                            if used_field_names.contains(&name) {
+                                return Err(ParseErrorKind::DuplicateField(name));
+                            }
+                            f.position = used_field_names.len();
+                            used_field_names.push(name);
+                            Ok((f, source))
+                        }),
+                    )),
+                    preceded(multispace0, tag("}")),
+                ),
             ),
-        ),
-    ))(input)?;
-    let mut name = Name::new(name).unwrap();
+        ))(input)?;
+    let (fields, field_sources): (Vec<RecordField>, Vec<FieldSource>) = fields.into_iter().unzip();
+    let source = DeclarationSource {
+        names: std::iter::once(name)
+            .chain(field_sources.iter().map(|field| field.name))
+            .collect(),
+        types: field_sources.iter().map(|field| field.schema).collect(),
+        fields: field_sources,
+    };
+    let mut name = parsed_name(name);
 
     name.namespace = namespace;
+    attributes.extend(more_attributes);
+
+    // Fields are found by their name or any of their aliases
+    let mut lookup = BTreeMap::new();
+    for field in fields.iter() {
+        lookup.insert(field.name.clone(), field.position);
+        for alias in field.aliases.iter().flatten() {
+            lookup.insert(alias.clone(), field.position);
+        }
+    }
 
     Ok((
         tail,
-        Schema::Record(RecordSchema {
-            name: name,
-            aliases: aliases,
-            doc: doc,
-            fields: fields,
-            lookup: BTreeMap::new(),
-            attributes: BTreeMap::new(),
-        }),
+        (
+            Schema::Record(RecordSchema {
+                name,
+                aliases,
+                doc,
+                fields,
+                lookup,
+                attributes,
+            }),
+            source,
+        ),
     ))
 }
 
 #[derive(Error, Debug)]
-enum AvdlError {
+pub enum AvdlError {
     #[error("Failed to import Avsc")]
     ImportAvscError(#[from] apache_avro::Error),
 
-    #[error("Failed to import Avdl")]
-    ImportIdlError,
+    #[error("Failed to import Avdl, {0}")]
+    ImportIdlError(ParseError),
+
+    #[error("Circular import of {}", .0.display())]
+    CircularImport(PathBuf),
+
+    #[error("Unknown type {}", .0.fullname(None))]
+    UnresolvedReference(Name),
+
+    #[error("Failed to import Avpr")]
+    ImportAvprError(#[from] serde_json::Error),
+
+    #[error("Failed to read the file")]
+    ReadError(#[from] std::io::Error),
+
+    #[error("The input is not valid UTF-8")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+
+    #[error("Invalid schema, {0}")]
+    InvalidSchema(String),
+
+    #[error("Failed to parse Avdl")]
+    ParseIdlError(#[from] ParseError),
+}
+
+/// Options changing how an Avdl source is parsed. The default is lenient.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseOptions {
+    /// Reject named types using a keyword as their name, e.g. `record int` or `enum void`.
+    /// In lenient mode such a type can only be referenced by its fullname.
+    pub strict: bool,
+    /// Directory the paths of `import` statements are relative to, the current
+    /// directory when `None`.
+    pub import_dir: Option<PathBuf>,
+    /// Allow Unicode letters and digits in names, Avro only allows `[A-Za-z0-9_]`.
+    pub unicode_names: bool,
+    /// Only write a named type in full where it first appears, types then
+    /// messages in order, like `.avsc` files do. Its later uses are kept as a
+    /// `Schema::Ref` to its fullname, a declared type already written inside a
+    /// previous one included.
+    pub reference_named_types: bool,
+}
+
+// Keywords of the language, in strict mode no named type can use them
+const KEYWORDS: [&str; 32] = [
+    "null",
+    "boolean",
+    "int",
+    "long",
+    "float",
+    "double",
+    "bytes",
+    "string",
+    "array",
+    "map",
+    "union",
+    "date",
+    "uuid",
+    "time_ms",
+    "timestamp_ms",
+    "time_us",
+    "timestamp_us",
+    "decimal",
+    "void",
+    "true",
+    "false",
+    "record",
+    "error",
+    "enum",
+    "fixed",
+    "protocol",
+    "namespace",
+    "import",
+    "idl",
+    "schema",
+    "throws",
+    "oneway",
+];
+
+// The first of `names` using more than `[A-Za-z0-9_]`
+fn find_non_ascii_name<'a>(names: &[&'a str]) -> Option<&'a str> {
+    names
+        .iter()
+        .copied()
+        .find(|name| !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+fn type_kind(schema: &Schema) -> TypeKind {
+    match schema {
+        Schema::Enum(_) => TypeKind::Enum,
+        Schema::Fixed(_) => TypeKind::Fixed,
+        _ => TypeKind::Record,
+    }
+}
+
+fn check_type_name(schema: Schema, options: &ParseOptions) -> Result<Schema, String> {
+    let name = match &schema {
+        Schema::Record(RecordSchema { name, .. })
+        | Schema::Enum(EnumSchema { name, .. })
+        | Schema::Fixed(FixedSchema { name, .. }) => &name.name,
+        _ => return Ok(schema),
+    };
+    if options.strict && KEYWORDS.contains(&name.as_str()) {
+        return Err(format!("Type name {name} is a reserved keyword"));
+    }
+    Ok(schema)
+}
+
+/// The kind of a named type declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Record,
+    Enum,
+    Fixed,
+}
+
+impl std::fmt::Display for TypeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeKind::Record => write!(f, "record"),
+            TypeKind::Enum => write!(f, "enum"),
+            TypeKind::Fixed => write!(f, "fixed"),
+        }
+    }
+}
+
+/// What went wrong in a `ParseError`
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    #[error("Duplicate field {0}")]
+    DuplicateField(String),
+
+    /// A named type declared twice, `previous` is the kind of the first declaration
+    #[error("Duplicate {kind} {}, conflicts with an earlier {previous}", .name.fullname(None))]
+    DuplicateType {
+        name: Name,
+        kind: TypeKind,
+        previous: TypeKind,
+    },
+
+    #[error("Invalid default for {field}, {reason}")]
+    InvalidDefault { field: String, reason: String },
+
+    /// A reference to a type declared nowhere, neither in the source nor in
+    /// its imports
+    #[error("Unknown type {}", .0.fullname(None))]
+    UnresolvedReference(Name),
+
+    /// Any other problem, described by its message
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for ParseErrorKind {
+    fn from(message: String) -> Self {
+        ParseErrorKind::Other(message)
+    }
+}
+
+/// Error returned when an Avdl source can't be parsed. `offset` is the byte
+/// position in the source where the parser gave up, `line` and `column` are
+/// the same position 1-based, with `column` counted in characters.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{message} at line {line}, column {column}")]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// The description of `kind`
+    pub message: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl ParseError {
+    fn new(source: &str, offset: usize, kind: ParseErrorKind) -> Self {
+        let consumed = &source[..offset];
+        let line_start = consumed.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        ParseError {
+            message: kind.to_string(),
+            kind,
+            offset,
+            line: consumed.matches('\n').count() + 1,
+            column: consumed[line_start..].chars().count() + 1,
+        }
+    }
+
+    fn from_nom(source: &str, e: nom::Err<InputError<&str>>) -> Self {
+        match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                let kind = match (e.reason, e.message) {
+                    (Some(reason), _) => reason,
+                    (None, Some(message)) => ParseErrorKind::Other(message),
+                    (None, None) => {
+                        ParseErrorKind::Other(format!("Unexpected input, {}", e.kind.description()))
+                    }
+                };
+                ParseError::new(source, source.len() - e.input.len(), kind)
+            }
+            nom::Err::Incomplete(_) => ParseError::new(
+                source,
+                source.len(),
+                ParseErrorKind::Other(String::from("Unexpected end of input")),
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -943,27 +2055,84 @@ enum Import {
     Schema,
 }
 
+// `importing` holds the Avdl files whose imports are being solved, to stop on
+// files importing each other.
 fn import_solver(
-    importType: Import,
-    path: String,
-    names_ref: &mut HashMap<Name, Schema>,
+    import_type: Import,
+    path: &Path,
+    importing: &[PathBuf],
 ) -> Result<Vec<Schema>, AvdlError> {
-    let input = fs::read_to_string(path).expect("Failed to read the file");
-    match importType {
+    let path = fs::canonicalize(path)?;
+    if importing.contains(&path) {
+        return Err(AvdlError::CircularImport(path));
+    }
+    let contents = fs::read_to_string(&path)?;
+    let input = strip_bom(&contents);
+    match import_type {
         Import::Idl => {
-            let (_, (schemas, _namespace)) =
-                parse_protocol(input.as_str(), names_ref).map_err(|_| AvdlError::ImportIdlError)?;
-            return Ok(schemas);
+            let options = ParseOptions {
+                import_dir: path.parent().map(Path::to_path_buf),
+                ..Default::default()
+            };
+            let importing = [importing, std::slice::from_ref(&path)].concat();
+            let mut names_ref = HashMap::new();
+            let mut errors = Vec::new();
+            let (_, protocol) = parse_protocol_collecting(
+                input,
+                &mut names_ref,
+                &options,
+                &mut errors,
+                &mut Sources::default(),
+                &importing,
+            )
+            .and_then(|parsed| match errors.into_iter().next() {
+                Some(e) => Err(nom::Err::Failure(e)),
+                None => Ok(parsed),
+            })
+            .map_err(|e| AvdlError::ImportIdlError(ParseError::from_nom(input, e)))?;
+            Ok(protocol.types)
         }
-        Import::Protocol => todo!(),
-        Import::Schema => Ok(vec![Schema::parse_str(input.as_str())?]),
+        Import::Protocol => parse_avpr_types(input),
+        Import::Schema => Ok(vec![Schema::parse_str(input)?]),
     }
 }
 
-fn parse_import(input: &str) -> IResult<&str, (Import, String)> {
-    preceded(
-        space_or_comment_delimited(tag("import")),
-        terminated(
+// Sample:
+// ```
+// {"protocol": "Greetings", "namespace": "org.example", "types": [...], "messages": {}}
+// ```
+// Only the `types` are kept, the ones without a namespace take the one of the protocol.
+fn parse_avpr_types(input: &str) -> Result<Vec<Schema>, AvdlError> {
+    let protocol: Value = serde_json::from_str(input)?;
+    let namespace = protocol.get("namespace").and_then(Value::as_str);
+    let types = match protocol.get("types").and_then(Value::as_array) {
+        Some(types) => types,
+        None => return Ok(vec![]),
+    };
+    let types = types
+        .iter()
+        .map(|t| {
+            let mut t = t.clone();
+            if let (Some(namespace), Some(object)) = (namespace, t.as_object_mut()) {
+                let qualified = object
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .is_some_and(|name| name.contains('.'));
+                if !qualified && !object.contains_key("namespace") {
+                    object.insert(String::from("namespace"), Value::from(namespace));
+                }
+            }
+            t.to_string()
+        })
+        .collect::<Vec<String>>();
+    let types: Vec<&str> = types.iter().map(String::as_str).collect();
+    Ok(Schema::parse_list(&types)?)
+}
+
+fn parse_import(input: &str) -> IResult<&str, (Import, String)> {
+    preceded(
+        space_or_comment_delimited(tag("import")),
+        terminated(
             tuple((
                 space_or_comment_delimited(alt((
                     value(Import::Idl, tag("idl")),
@@ -977,17 +2146,145 @@ fn parse_import(input: &str) -> IResult<&str, (Import, String)> {
     )(input)
 }
 
-fn parse_import_into_schema(input: &str) -> IResult<&str, Vec<Schema>> {
-    map_res(
-        parse_import,
-        |(import, name)| -> Result<Vec<Schema>, String> {
-            match import {
-                Import::Idl => todo!(),
-                Import::Protocol => todo!(),
-                Import::Schema => todo!(),
+// Load the types of an import statement, `import_dir` is the directory the
+// path is relative to.
+fn load_import(
+    import: Import,
+    path: &str,
+    import_dir: &Option<PathBuf>,
+    importing: &[PathBuf],
+) -> Result<Vec<Schema>, String> {
+    let path = match import_dir {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    };
+    import_solver(import, &path, importing).map_err(|e| match e {
+        AvdlError::CircularImport(_) => e.to_string(),
+        e => format!("{e} {}", path.display()),
+    })
+}
+
+// Register `schema` and every named type declared inside it, so they can be
+// referenced by name. Names already taken are kept.
+fn register_named_types(schema: &Schema, names_ref: &mut HashMap<Name, Schema>) {
+    match schema {
+        Schema::Record(RecordSchema { name, fields, .. }) => {
+            names_ref
+                .entry(name.clone())
+                .or_insert_with(|| schema.clone());
+            for field in fields {
+                register_named_types(&field.schema, names_ref);
             }
-        },
-    )(input)
+        }
+        Schema::Enum(EnumSchema { name, .. }) | Schema::Fixed(FixedSchema { name, .. }) => {
+            names_ref
+                .entry(name.clone())
+                .or_insert_with(|| schema.clone());
+        }
+        Schema::Array(schema) | Schema::Map(schema) => register_named_types(schema, names_ref),
+        Schema::Union(union_schema) => {
+            for variant in union_schema.variants() {
+                register_named_types(variant, names_ref);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Items that can be declared in the body of a protocol
+enum Declaration<'a> {
+    Type(Schema, DeclarationSource<'a>),
+    Message(Message, DeclarationSource<'a>),
+    // Kept with the input at the statement to report a failed import
+    Import(Import, String, &'a str),
+}
+
+// Messages without a response return `void`, kept as `null`
+fn parse_message_response(input: &str) -> IResult<&str, Schema> {
+    alt((
+        value(
+            Schema::Null,
+            space_or_comment_delimited(verify(parse_var_name, |name: &str| name == "void")),
+        ),
+        space_or_comment_delimited(map_type_to_schema),
+    ))(input)
+}
+
+// Sample:
+// ```
+// string hello(string name, int age);
+// void ping();
+// void fireAndForget(string msg) oneway;
+// Greeting hello(string name) throws NotFound, Forbidden;
+// ```
+fn parse_message_located(input: &str) -> IResult<&str, (Message, DeclarationSource<'_>)> {
+    let (tail, (doc, (response_source, response), name, params, errors, one_way)) = terminated(
+        tuple((
+            parse_declaration_doc,
+            consumed(parse_message_response),
+            space_or_comment_delimited(parse_var_name),
+            delimited(
+                tag("("),
+                separated_list0(tag(","), parse_message_param),
+                preceded(multispace0, tag(")")),
+            ),
+            map(
+                opt(preceded(
+                    space_or_comment_delimited(tag("throws")),
+                    separated_list1(tag(","), consumed(parse_reference)),
+                )),
+                Option::unwrap_or_default,
+            ),
+            map(opt(space_or_comment_delimited(tag("oneway"))), |o| {
+                o.is_some()
+            }),
+        )),
+        space_or_comment_delimited(tag(";")),
+    )(input)?;
+
+    if one_way && response != Schema::Null {
+        return Err(nom::Err::Failure(InputError::from_external_error(
+            input,
+            ErrorKind::Verify,
+            format!("Oneway message {name} must return void"),
+        )));
+    }
+
+    let (request, fields): (Vec<RecordField>, Vec<FieldSource>) = params.into_iter().unzip();
+    let (error_sources, errors): (Vec<&str>, Vec<Schema>) = errors.into_iter().unzip();
+    let integral_defaults = request
+        .iter()
+        .zip(&fields)
+        .filter(|(param, source)| is_integral_default(&param.schema, source.default))
+        .map(|(param, _source)| param.name.clone())
+        .collect();
+    let source = DeclarationSource {
+        names: std::iter::once(name)
+            .chain(fields.iter().map(|field| field.name))
+            .collect(),
+        types: fields
+            .iter()
+            .map(|field| field.schema)
+            .chain(std::iter::once(response_source))
+            .chain(error_sources)
+            .collect(),
+        fields,
+    };
+    Ok((
+        tail,
+        (
+            Message {
+                name: name.to_string(),
+                doc,
+                request,
+                response,
+                errors,
+                one_way,
+                integral_defaults,
+            },
+            source,
+        ),
+    ))
 }
 
 // Sample:
@@ -1002,9 +2299,46 @@ fn parse_import_into_schema(input: &str) -> IResult<&str, Vec<Schema>> {
 pub fn parse_protocol<'a>(
     input: &'a str,
     names_ref: &mut HashMap<Name, Schema>,
-) -> IResult<&'a str, (Vec<Schema>, Namespace)> {
-    let (tail, (_doc, namespace, _name, schemas)) = tuple((
-        opt(parse_doc),
+    options: &ParseOptions,
+) -> IResult<&'a str, Protocol> {
+    let mut errors = Vec::new();
+    let (tail, protocol) = parse_protocol_collecting(
+        input,
+        names_ref,
+        options,
+        &mut errors,
+        &mut Sources::default(),
+        &[],
+    )?;
+    match errors.into_iter().next() {
+        Some(e) => Err(nom::Err::Failure(e)),
+        None => Ok((tail, protocol)),
+    }
+}
+
+// Same as `parse_protocol`, but errors that don't stop the parsing, like
+// duplicated names, are pushed to `errors`, and where the declarations are
+// written is kept in `sources`. `importing` are the Avdl files being imported,
+// see `import_solver`.
+//
+// Without the `protocol` wrapper the input is a schema file, only declaring
+// types:
+// ```
+// namespace org.example;
+// enum Kind { A, B }
+// record Main { Kind kind; }
+// ```
+fn parse_protocol_collecting<'a>(
+    input: &'a str,
+    names_ref: &mut HashMap<Name, Schema>,
+    options: &ParseOptions,
+    errors: &mut Vec<InputError<&'a str>>,
+    sources: &mut Sources<'a>,
+    importing: &[PathBuf],
+) -> IResult<&'a str, Protocol> {
+    let mut error_names = Vec::new();
+    let (tail, header) = opt(tuple((
+        opt(preceded(multispace0, parse_doc)),
         space_or_comment_delimited(opt(parse_namespace)),
         preceded(
             multispace0,
@@ -1013,83 +2347,537 @@ pub fn parse_protocol<'a>(
                 space_delimited(parse_var_name),
             ),
         ),
-        delimited(
-            space_delimited(tag("{")),
-            many1(space_or_comment_delimited(map_res(
-                alt((parse_record, parse_enum, parse_fixed)),
-                |mut schema| match &mut schema {
-                    Schema::Record(RecordSchema {
-                        name,
-                        aliases: _,
-                        doc: _,
-                        fields: _,
-                        lookup: _,
-                        attributes: _,
-                    }) => {
-                        // name.namespace = Some("cagon.org".to_string());
-                        let name = name.clone();
-                        if names_ref.contains_key(&name) {
-                            return Err("Duplicate field {name}");
-                        }
+        space_delimited(tag("{")),
+    )))(input)?;
+    let in_protocol = header.is_some();
+    if let Some((_, _, name, _)) = header {
+        sources.names.push(name);
+    }
+    let (tail, file_namespace) = if in_protocol {
+        (tail, None)
+    } else {
+        opt(parse_namespace_statement)(tail)?
+    };
+    // Namespace of the declarations without their own, to name them in errors
+    let declared_namespace = match &header {
+        Some((_, namespace, _, _)) => namespace.clone(),
+        None => file_namespace.clone(),
+    };
+    let (tail, declarations) = many1(preceded(
+        blank_before_declaration,
+        alt((
+            map(
+                |i: &'a str| {
+                    let (tail, (schema, source)) = map_res_cut(
+                        alt((
+                            |i| parse_record_with_keyword(i, "record"),
+                            parse_enum_located,
+                            parse_fixed_located,
+                            map(
+                                |i| parse_record_with_keyword(i, "error"),
+                                |(schema, source)| {
+                                    if let Schema::Record(RecordSchema { name, .. }) = &schema {
+                                        error_names.push(name.clone());
+                                    }
+                                    (schema, source)
+                                },
+                            ),
+                        )),
+                        |(schema, source)| {
+                            check_type_name(schema, options).map(|schema| (schema, source))
+                        },
+                    )(i)?;
+                    let name = match &schema {
+                        Schema::Record(RecordSchema { name, .. })
+                        | Schema::Enum(EnumSchema { name, .. })
+                        | Schema::Fixed(FixedSchema { name, .. }) => name.clone(),
+                        _ => unreachable!("Only named types are declared in a protocol"),
+                    };
+                    if let Some(previous) = names_ref.get(&name) {
+                        errors.push(InputError::from_reason(
+                            i,
+                            ErrorKind::Verify,
+                            ParseErrorKind::DuplicateType {
+                                name: name.fully_qualified_name(&declared_namespace),
+                                kind: type_kind(&schema),
+                                previous: type_kind(previous),
+                            },
+                        ));
+                    } else {
                         names_ref.insert(name, schema.clone());
-                        return Ok(schema);
                     }
-                    Schema::Fixed(FixedSchema {
-                        name,
-                        aliases: _,
-                        doc: _,
-                        size: _,
-                        attributes: _,
-                    }) => {
-                        let name = name.clone();
-                        if names_ref.contains_key(&name) {
-                            return Err("Duplicate field {name}");
-                        }
-                        names_ref.insert(name, schema.clone());
-                        return Ok(schema);
+                    Ok((tail, (schema, source)))
+                },
+                |(schema, source)| Declaration::Type(schema, source),
+            ),
+            map(
+                // Schema files only declare types
+                |i| {
+                    if in_protocol {
+                        parse_message_located(i)
+                    } else {
+                        fail(i)
                     }
-                    Schema::Enum(EnumSchema {
-                        name,
-                        aliases: _,
-                        doc: _,
-                        symbols: _,
-                        attributes: _,
-                        default: _,
-                    }) => {
-                        let name = name.clone();
-                        if names_ref.contains_key(&name) {
-                            return Err("Duplicate field {name}");
-                        }
-                        names_ref.insert(name, schema.clone());
-                        return Ok(schema);
+                },
+                |(message, source)| Declaration::Message(message, source),
+            ),
+            |i: &'a str| {
+                let (tail, (import, path)) = parse_import(i)?;
+                Ok((tail, Declaration::Import(import, path, i)))
+            },
+        )),
+    ))(tail)?;
+    let (tail, name, doc, namespace) = match header {
+        Some((doc, namespace, name, _)) => {
+            let (tail, _) = preceded(blank, tag("}"))(tail)?;
+            (tail, name.to_string(), doc, namespace)
+        }
+        None => (tail, String::new(), None, file_namespace),
+    };
+
+    let mut types = Vec::new();
+    let mut messages = Vec::new();
+    let mut integral_defaults = Vec::new();
+    for declaration in declarations {
+        match declaration {
+            Declaration::Type(schema, source) => {
+                sources.names.extend(source.names);
+                sources.types.extend(source.types);
+                if let Schema::Record(RecordSchema { name, fields, .. }) = &schema {
+                    if options.strict {
+                        errors.extend(time_of_day_error(fields, &source.fields));
                     }
-                    Schema::Ref { name } => {
-                        let name = name.clone();
-                        if names_ref.contains_key(&name) {
-                            return Err("Duplicate field {name}");
+                    integral_defaults.extend(
+                        fields
+                            .iter()
+                            .zip(&source.fields)
+                            .filter(|(field, source)| {
+                                is_integral_default(&field.schema, source.default)
+                            })
+                            .map(|(field, _source)| (name.clone(), field.name.clone())),
+                    );
+                    let name = name.fully_qualified_name(&declared_namespace);
+                    sources.fields.insert(name, source.fields);
+                }
+                types.push(schema);
+            }
+            Declaration::Message(message, source) => {
+                if options.strict {
+                    errors.extend(time_of_day_error(&message.request, &source.fields));
+                }
+                sources.names.extend(source.names);
+                sources.types.extend(source.types);
+                messages.push(message);
+            }
+            // Imported types become types of the protocol, as if declared here
+            Declaration::Import(import, path, i) => {
+                match load_import(import, &path, &options.import_dir, importing) {
+                    Ok(imported) => {
+                        for schema in imported.iter() {
+                            register_named_types(schema, names_ref);
                         }
-                        names_ref.insert(name, schema.clone());
-                        return Ok(schema);
+                        types.extend(imported);
                     }
-                    _ => todo!(),
-                },
-            ))),
-            preceded(multispace0, tag("}")),
-        ),
-    ))(input)?;
+                    Err(e) => errors.push(InputError::from_external_error(i, ErrorKind::Verify, e)),
+                }
+            }
+        }
+    }
+
+    Ok((
+        tail,
+        Protocol {
+            name,
+            doc,
+            namespace,
+            types,
+            error_names,
+            integral_defaults,
+            messages,
+        },
+    ))
+}
 
-    Ok((tail, (schemas, namespace)))
+// Files saved with a byte order mark start with it, it isn't part of the source.
+// Offsets of errors are counted without it.
+pub(crate) fn strip_bom(input: &str) -> &str {
+    input.strip_prefix('\u{feff}').unwrap_or(input)
 }
 
 pub fn parse(input: &str) -> IResult<&str, Vec<Schema>> {
+    let input = strip_bom(input);
+    let (tail, protocol) = parse_and_resolve(input, &ParseOptions::default())?;
+    Ok((tail, protocol.types))
+}
+
+// Parse a whole protocol and solve the references between its types, failing
+// on the first error
+fn parse_and_resolve<'a>(input: &'a str, options: &ParseOptions) -> IResult<&'a str, Protocol> {
+    let mut errors = Vec::new();
+    let (tail, protocol) = parse_and_resolve_collecting(input, options, &mut errors)?;
+    match errors.into_iter().next() {
+        Some(e) => Err(nom::Err::Failure(e)),
+        None => Ok((tail, protocol)),
+    }
+}
+
+fn parse_and_resolve_collecting<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+    errors: &mut Vec<InputError<&'a str>>,
+) -> IResult<&'a str, Protocol> {
+    let (tail, protocol) = parse_and_resolve_protocol(input, options, errors)?;
+    let (tail, _) = many0(alt((multispace1, parse_comment)))(tail)?;
+    if !tail.is_empty() {
+        return Err(nom::Err::Failure(InputError {
+            input: tail,
+            kind: ErrorKind::Eof,
+            message: Some(String::from("Unexpected input after the protocol")),
+            reason: None,
+        }));
+    }
+    Ok((tail, protocol))
+}
+
+// Parse one protocol and solve the references between its types, the input
+// may go on after it
+fn parse_and_resolve_protocol<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+    errors: &mut Vec<InputError<&'a str>>,
+) -> IResult<&'a str, Protocol> {
     let mut names_ref = HashMap::new();
-    let (_, (mut schemas, namespace)) = parse_protocol(input, &mut names_ref)?;
+    let mut sources = Sources::default();
+    let (tail, mut protocol) =
+        parse_protocol_collecting(input, &mut names_ref, options, errors, &mut sources, &[])?;
+    // Errors without a source, like those of imported types, point at the end
+    // of the protocol, which is where `tail` starts
+    if !options.unicode_names {
+        if let Some(name) = find_non_ascii_name(&sources.names) {
+            errors.push(InputError::from_external_error(
+                &input[input.offset(name)..],
+                ErrorKind::Verify,
+                format!("Name {name} is not allowed, only ASCII letters, digits and _ are"),
+            ));
+        }
+    }
+
+    // Types are registered by their fullname within the protocol namespace,
+    // which they keep wherever they are inlined
+    let mut names_ref: HashMap<Name, Schema> = names_ref
+        .into_iter()
+        .map(|(name, mut schema)| {
+            namespace_solver(&mut schema, &protocol.namespace);
+            (name.fully_qualified_name(&protocol.namespace), schema)
+        })
+        .collect();
+    // and by their aliases, relative to their namespace, unless taken by a type
+    let aliased: Vec<(Name, Schema)> = names_ref
+        .iter()
+        .flat_map(|(name, schema)| {
+            aliases_of(schema)
+                .iter()
+                .map(|alias| (alias.fully_qualified_name(&name.namespace), schema.clone()))
+        })
+        .collect();
+    for (alias, schema) in aliased {
+        names_ref.entry(alias).or_insert(schema);
+    }
+    for schema in protocol.types.iter_mut() {
+        if let Err(e) = schema_solver(schema, &mut names_ref, &protocol.namespace, &[]) {
+            errors.push(solver_error(input, tail, &sources, e));
+        }
+        namespace_solver(schema, &protocol.namespace);
+    }
+    // Same namespace as set by `namespace_solver` on the records
+    let names = protocol.error_names.iter_mut().chain(
+        protocol
+            .integral_defaults
+            .iter_mut()
+            .map(|(name, _field)| name),
+    );
+    for name in names {
+        if name.namespace.is_none() {
+            name.namespace = protocol.namespace.clone();
+        }
+    }
+    for schema in protocol.types.iter_mut() {
+        let namespace = &protocol.namespace;
+        let res = enum_default_solver(schema, namespace, options)
+            .and_then(|()| fixed_default_solver(schema, namespace))
+            .and_then(|()| record_default_solver(schema, namespace));
+        if let Err(e) = res {
+            errors.push(default_error(input, tail, &sources, e));
+        }
+    }
+    for message in protocol.messages.iter_mut() {
+        let schemas = message
+            .request
+            .iter_mut()
+            .map(|param| &mut param.schema)
+            .chain(std::iter::once(&mut message.response))
+            .chain(message.errors.iter_mut());
+        for schema in schemas {
+            let res = schema_solver(schema, &mut names_ref, &protocol.namespace, &[]);
+            match res {
+                Ok(Operation::Swap(solved)) => {
+                    *schema = solved;
+                }
+                Ok(Operation::NoOp) => {}
+                Err(e) => errors.push(solver_error(input, tail, &sources, e)),
+            }
+        }
+    }
+    if options.reference_named_types {
+        let mut declared = Vec::new();
+        let namespace = &protocol.namespace;
+        for schema in protocol.types.iter_mut() {
+            *schema = reference_declared(schema, &mut declared, namespace);
+        }
+        for message in protocol.messages.iter_mut() {
+            let schemas = message
+                .request
+                .iter_mut()
+                .map(|param| &mut param.schema)
+                .chain(std::iter::once(&mut message.response))
+                .chain(message.errors.iter_mut());
+            for schema in schemas {
+                *schema = reference_declared(schema, &mut declared, namespace);
+            }
+        }
+    }
+    Ok((tail, protocol))
+}
+
+// Point an error of the default solvers at the default of the field, at the
+// symbol at fault when it's in a collection. Imported records have no source,
+// their errors point at `end`.
+fn default_error<'a>(
+    input: &'a str,
+    end: &'a str,
+    sources: &Sources<'a>,
+    e: DefaultError,
+) -> InputError<&'a str> {
+    let default = sources
+        .fields
+        .get(&e.record)
+        .and_then(|fields| fields.get(e.field))
+        .and_then(|field| field.default);
+    let offset = match default {
+        Some(default) => input.offset(default) + token_offset(default, &e.symbol).unwrap_or(0),
+        None => input.offset(end),
+    };
+    InputError::from_external_error(&input[offset..], ErrorKind::Verify, e.reason)
+}
+
+// Offset of `token` in `source` as a whole word, leaving out comments, strings
+// and annotations
+fn token_offset(source: &str, token: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let skipped = if rest.starts_with("/*") {
+            rest.find("*/").map_or(rest.len(), |end| end + 2)
+        } else if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if c == '"' {
+            parse_string_uni::<InputError<&str>>(rest)
+                .map_or(1, |(tail, _)| rest.len() - tail.len())
+        } else if c == '@' {
+            1 + rest[1..]
+                .find(|c: char| !is_word(c) && c != '-')
+                .unwrap_or(rest.len() - 1)
+        } else if is_word(c) {
+            let word = &rest[..rest.find(|c: char| !is_word(c)).unwrap_or(rest.len())];
+            if word == token {
+                return Some(source.offset(rest));
+            }
+            word.len()
+        } else {
+            c.len_utf8()
+        };
+        rest = &rest[skipped..];
+    }
+    None
+}
+
+// A float or double default written as an integer, like `double x = 5;`
+fn is_integral_default(schema: &Schema, default: Option<&str>) -> bool {
+    let schema = match schema {
+        Schema::Union(union_schema) => union_schema.variants().first(),
+        schema => Some(schema),
+    };
+    matches!(schema, Some(Schema::Float | Schema::Double))
+        && default
+            .and_then(|default| float_literal(default).ok())
+            .is_some_and(|(_tail, literal)| literal.parse::<i64>().is_ok())
+}
+
+// In strict mode `time_ms` defaults are integers, not a time of day like
+// `"01:00:00"`. Those are found as the defaults that don't parse once `time_ms`
+// takes only integers.
+fn time_of_day_error<'a>(
+    fields: &[RecordField],
+    sources: &[FieldSource<'a>],
+) -> Option<InputError<&'a str>> {
+    fields.iter().zip(sources).find_map(|(field, source)| {
+        let default = source.default?;
+        let schema = Box::new(without_time_of_day(&field.schema));
+        parse_based_on_schema(schema)(default).err().map(|_| {
+            InputError::from_external_error(
+                default,
+                ErrorKind::Verify,
+                format!(
+                    "Default of time_ms field {} must be an integer in strict mode",
+                    field.name
+                ),
+            )
+        })
+    })
+}
+
+// `schema` with `time_ms` read as `int`, its default only depends on the
+// first type of a union
+fn without_time_of_day(schema: &Schema) -> Schema {
+    match schema {
+        Schema::TimeMillis => Schema::Int,
+        Schema::Array(items) => Schema::Array(Box::new(without_time_of_day(items))),
+        Schema::Map(values) => Schema::Map(Box::new(without_time_of_day(values))),
+        Schema::Union(union_schema) => union_schema
+            .variants()
+            .first()
+            .map_or_else(|| schema.clone(), without_time_of_day),
+        other => other.clone(),
+    }
+}
+
+// Point an error of `schema_solver` at the first use of the unknown type, or at
+// `end` if it is used in an imported file
+fn solver_error<'a>(
+    input: &'a str,
+    end: &'a str,
+    sources: &Sources<'a>,
+    kind: ParseErrorKind,
+) -> InputError<&'a str> {
+    let offset = match &kind {
+        ParseErrorKind::UnresolvedReference(name) => sources
+            .types
+            .iter()
+            .find_map(|schema| {
+                token_offset(schema, &name.fullname(None))
+                    .or_else(|| token_offset(schema, &name.name))
+                    .map(|offset| input.offset(schema) + offset)
+            })
+            .unwrap_or(input.offset(end)),
+        _ => input.offset(end),
+    };
+    InputError::from_reason(&input[offset..], ErrorKind::Verify, kind)
+}
+
+/// Parse an Avdl protocol, including its messages. Schema files, declaring
+/// types without a `protocol`, are parsed into a `Protocol` without a name.
+pub fn parse_idl(input: &str) -> Result<Protocol, ParseError> {
+    parse_idl_with_options(input, &ParseOptions::default())
+}
+
+/// Like `parse_idl`, with the given `options`.
+pub fn parse_idl_with_options(input: &str, options: &ParseOptions) -> Result<Protocol, ParseError> {
+    let input = strip_bom(input);
+    let (_tail, protocol) =
+        parse_and_resolve(input, options).map_err(|e| ParseError::from_nom(input, e))?;
+    Ok(protocol)
+}
+
+/// Parse an Avdl protocol like `parse_idl_with_options`, but instead of stopping
+/// on the first error report every error that doesn't prevent further parsing,
+/// like duplicated type names.
+pub fn validate(input: &str, options: &ParseOptions) -> Result<Protocol, Vec<ParseError>> {
+    let input = strip_bom(input);
+    let mut errors = Vec::new();
+    match parse_and_resolve_collecting(input, options, &mut errors) {
+        Ok((_tail, protocol)) if errors.is_empty() => Ok(protocol),
+        Ok(_) => Err(errors
+            .into_iter()
+            .map(|e| ParseError::from_nom(input, nom::Err::Failure(e)))
+            .collect()),
+        Err(e) => Err(vec![ParseError::from_nom(input, e)]),
+    }
+}
+
+/// Parse a sequence of Avdl protocols, as found in files concatenating several
+/// of them. Each protocol only sees the types it declares or imports.
+pub fn parse_protocols(input: &str) -> Result<Vec<Protocol>, ParseError> {
+    let input = strip_bom(input);
+    let options = ParseOptions::default();
+    let mut protocols = Vec::new();
+    let mut errors = Vec::new();
+    let mut tail = input;
+    loop {
+        let (start, _) = blank(tail).map_err(|e| ParseError::from_nom(input, e))?;
+        if start.is_empty() && !protocols.is_empty() {
+            break;
+        }
+        let (rest, protocol) = parse_and_resolve_protocol(start, &options, &mut errors)
+            .map_err(|e| ParseError::from_nom(input, e))?;
+        if let Some(e) = errors.into_iter().next() {
+            return Err(ParseError::from_nom(input, nom::Err::Failure(e)));
+        }
+        // Declarations out of any protocol would be taken for a schema file
+        if protocol.name.is_empty() {
+            return Err(ParseError::new(
+                input,
+                input.len() - start.len(),
+                ParseErrorKind::Other(String::from("Expected a protocol")),
+            ));
+        }
+        protocols.push(protocol);
+        errors = Vec::new();
+        tail = rest;
+    }
+    Ok(protocols)
+}
+
+/// Parse an Avdl protocol into its schemas.
+pub fn parse_schemas(input: &str) -> Result<Vec<Schema>, ParseError> {
+    let input = strip_bom(input);
+    let (_tail, schemas) = parse(input).map_err(|e| ParseError::from_nom(input, e))?;
+    Ok(schemas)
+}
+
+/// Read the Avdl file at `path` and parse it into schemas. Imports are relative
+/// to the directory of the file.
+pub fn parse_file(path: impl AsRef<Path>) -> Result<Vec<Schema>, AvdlError> {
+    let path = path.as_ref();
+    let input = fs::read_to_string(path)?;
+    let options = ParseOptions {
+        import_dir: path.parent().map(Path::to_path_buf),
+        ..Default::default()
+    };
+    let protocol = parse_idl_with_options(&input, &options)?;
+    Ok(protocol.types)
+}
+
+/// Read an Avdl protocol from `reader`, e.g. a network stream, and parse it into
+/// schemas.
+pub fn parse_reader<R: Read>(mut reader: R) -> Result<Vec<Schema>, AvdlError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    parse_bytes(&bytes)
+}
+
+/// Parse the UTF-8 encoded Avdl protocol of `bytes` into schemas.
+pub fn parse_bytes(bytes: &[u8]) -> Result<Vec<Schema>, AvdlError> {
+    let input = std::str::from_utf8(bytes)?;
+    Ok(parse_schemas(input)?)
+}
 
-    for schema in schemas.iter_mut() {
-        let _ = schema_solver(schema, &mut names_ref, &None);
-        namespace_solver(schema, &namespace);
+fn aliases_of(schema: &Schema) -> &[Alias] {
+    match schema {
+        Schema::Record(RecordSchema { aliases, .. })
+        | Schema::Enum(EnumSchema { aliases, .. })
+        | Schema::Fixed(FixedSchema { aliases, .. }) => aliases.as_deref().unwrap_or_default(),
+        _ => &[],
     }
-    Ok(("", schemas))
 }
 
 enum Operation {
@@ -1097,18 +2885,85 @@ enum Operation {
     Swap(Schema),
 }
 
+/// Check that `apache_avro` accepts `schema`: it's written as JSON, with each
+/// named type declared once and referenced afterwards, then read back and
+/// compared to what was written.
+pub fn validate_schema(schema: &Schema) -> Result<(), AvdlError> {
+    let written = reference_declared(schema, &mut Vec::new(), &None);
+    let json =
+        serde_json::to_string(&written).map_err(|e| AvdlError::InvalidSchema(e.to_string()))?;
+    let reparsed = Schema::parse_str(&json).map_err(|e| AvdlError::InvalidSchema(e.to_string()))?;
+    if reparsed.canonical_form() != written.canonical_form() {
+        return Err(AvdlError::InvalidSchema(format!(
+            "it reads back from its JSON as {}",
+            reparsed.canonical_form()
+        )));
+    }
+    Ok(())
+}
+
+/// Check that every reference of `schemas` is a type of `names_ref`, as left by
+/// `parse_protocol`, without inlining them like `parse` does.
+pub fn validate_references(
+    schemas: &[Schema],
+    names_ref: &HashMap<Name, Schema>,
+) -> Result<(), AvdlError> {
+    for schema in schemas {
+        reference_validator(schema, names_ref, &None)?;
+    }
+    Ok(())
+}
+
+fn reference_validator(
+    schema: &Schema,
+    names_ref: &HashMap<Name, Schema>,
+    enclosing_namespace: &Namespace,
+) -> Result<(), AvdlError> {
+    match schema {
+        Schema::Record(RecordSchema { name, fields, .. }) => {
+            let record_namespace = name.fully_qualified_name(enclosing_namespace).namespace;
+            for field in fields {
+                reference_validator(&field.schema, names_ref, &record_namespace)?;
+            }
+            Ok(())
+        }
+        Schema::Array(schema) | Schema::Map(schema) => {
+            reference_validator(schema, names_ref, enclosing_namespace)
+        }
+        Schema::Union(union_schema) => {
+            for variant in union_schema.variants() {
+                reference_validator(variant, names_ref, enclosing_namespace)?;
+            }
+            Ok(())
+        }
+        Schema::Ref { name } => {
+            let fully_qualified_name = name.fully_qualified_name(enclosing_namespace);
+            if names_ref.contains_key(&fully_qualified_name) {
+                Ok(())
+            } else {
+                Err(AvdlError::UnresolvedReference(fully_qualified_name))
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+// `parents` are the records being solved, a reference to one of them is left
+// as is instead of expanding the record into itself.
 fn schema_solver(
     schema: &mut Schema,
     names_ref: &mut HashMap<Name, Schema>,
     enclosing_namespace: &Namespace,
-) -> Result<Operation, String> {
+    parents: &[Name],
+) -> Result<Operation, ParseErrorKind> {
     match schema {
         Schema::Record(RecordSchema { name, fields, .. }) => {
             let fully_qualified_name = name.fully_qualified_name(enclosing_namespace);
 
-            let record_namespace = fully_qualified_name.namespace;
+            let record_namespace = fully_qualified_name.namespace.clone();
+            let parents = [parents, &[fully_qualified_name]].concat();
             for field in fields {
-                let res = schema_solver(&mut field.schema, names_ref, &record_namespace)?;
+                let res = schema_solver(&mut field.schema, names_ref, &record_namespace, &parents)?;
                 match res {
                     Operation::Swap(schema) => {
                         field.schema = schema;
@@ -1118,50 +2973,357 @@ fn schema_solver(
             }
             Ok(Operation::NoOp)
         }
+        Schema::Array(items) => {
+            let res = schema_solver(items, names_ref, enclosing_namespace, parents)?;
+            if let Operation::Swap(schema) = res {
+                **items = schema;
+            }
+            Ok(Operation::NoOp)
+        }
+        Schema::Map(values) => {
+            let res = schema_solver(values, names_ref, enclosing_namespace, parents)?;
+            if let Operation::Swap(schema) = res {
+                **values = schema;
+            }
+            Ok(Operation::NoOp)
+        }
+        Schema::Union(union_schema) => {
+            let mut variants = union_schema.variants().to_vec();
+            for variant in variants.iter_mut() {
+                let res = schema_solver(variant, names_ref, enclosing_namespace, parents)?;
+                if let Operation::Swap(schema) = res {
+                    *variant = schema;
+                }
+            }
+            let union_schema =
+                UnionSchema::new(variants).map_err(|e| ParseErrorKind::Other(e.to_string()))?;
+            Ok(Operation::Swap(Schema::Union(union_schema)))
+        }
         Schema::Ref { name } => {
             let fully_qualified_name = name.fully_qualified_name(enclosing_namespace);
-            let found_schema = names_ref
-                .get(&fully_qualified_name)
-                .ok_or("Failed to solve schema".to_string())?;
-            Ok(Operation::Swap(found_schema.clone()))
+            if parents.contains(&fully_qualified_name) {
+                return Ok(Operation::NoOp);
+            }
+            let (key, found_schema) = names_ref
+                .get_key_value(&fully_qualified_name)
+                .ok_or_else(|| ParseErrorKind::UnresolvedReference(fully_qualified_name.clone()))?;
+            let namespace = key.namespace.clone();
+            let mut found_schema = found_schema.clone();
+            // Also reached through an alias
+            if let Schema::Record(RecordSchema { name, .. }) = &found_schema {
+                if parents.contains(&name.fully_qualified_name(&namespace)) {
+                    return Ok(Operation::NoOp);
+                }
+            }
+            // The references of the type are solved as well, whether the types
+            // they name are declared before or after it
+            schema_solver(&mut found_schema, names_ref, &namespace, parents)?;
+            Ok(Operation::Swap(found_schema))
         }
         _ => Ok(Operation::NoOp),
     }
 }
 
-fn namespace_solver(schema: &mut Schema, enclosing_namespace: &Namespace) -> () {
-    match schema {
-        Schema::Record(RecordSchema { name, .. }) => {
-            name.namespace = enclosing_namespace.clone();
+// A default found wrong once the types are resolved, in the field at `field`
+// of the record named `record`. `symbol` is the part of the default at fault.
+#[derive(Debug)]
+struct DefaultError {
+    record: Name,
+    field: usize,
+    symbol: String,
+    reason: String,
+}
+
+impl DefaultError {
+    fn new(
+        record: &Name,
+        enclosing_namespace: &Namespace,
+        field: &RecordField,
+        (symbol, reason): (String, String),
+    ) -> Self {
+        DefaultError {
+            record: record.fully_qualified_name(enclosing_namespace),
+            field: field.position,
+            symbol,
+            reason,
         }
-        _ => (),
     }
 }
 
-#[cfg(test)]
-mod test {
-    use std::collections::BTreeMap;
-
-    use super::*;
-    use apache_avro::schema::{Alias, Name, RecordField, RecordFieldOrder, Schema};
-    use rstest::rstest;
-    use serde_json::{Map, Number, Value};
-
-    #[rstest]
-    #[case("// holis\n", " holis")]
-    #[case(
-        "// TODO: Move to another place, etc.\n",
-        " TODO: Move to another place, etc."
-    )]
-    #[case("/*Som343f */", "Som343f ")]
-    #[case("//Som343f\n", "Som343f")]
-    #[case("/* holis */", " holis ")]
-    #[case(
-        "/* TODO: Move to another place, etc. */",
-        " TODO: Move to another place, etc. "
-    )]
-    fn test_parse_comment_ok<'a>(#[case] input: &str, #[case] expected: &str) {
-        assert_eq!(parse_comment::<'a, &str, ()>(input), Ok(("", expected)));
+// Check the symbols of enum defaults, also the items of array defaults, against
+// the resolved enum. Qualified symbols like `org.x.Suit.HEARTS` are stripped
+// down to the symbol, only allowed in lenient mode.
+fn enum_default_solver(
+    schema: &mut Schema,
+    enclosing_namespace: &Namespace,
+    options: &ParseOptions,
+) -> Result<(), DefaultError> {
+    if let Schema::Record(RecordSchema { name, fields, .. }) = schema {
+        for field in fields.iter_mut() {
+            enum_default_solver(&mut field.schema, enclosing_namespace, options)?;
+            let default = match (&field.schema, &field.default) {
+                (Schema::Enum(enum_schema), Some(Value::String(default))) => {
+                    enum_symbol(enum_schema, default, enclosing_namespace, options)
+                }
+                (Schema::Array(items), Some(Value::Array(defaults))) => match items.as_ref() {
+                    Schema::Enum(enum_schema) => defaults
+                        .iter()
+                        .map(|default| match default {
+                            Value::String(default) => {
+                                enum_symbol(enum_schema, default, enclosing_namespace, options)
+                            }
+                            other => {
+                                let symbol = other.to_string();
+                                let reason =
+                                    unknown_symbol(enum_schema, &symbol, enclosing_namespace);
+                                Err((symbol, reason))
+                            }
+                        })
+                        .collect::<Result<Vec<Value>, _>>()
+                        .map(Value::Array),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let default =
+                default.map_err(|e| DefaultError::new(name, enclosing_namespace, field, e))?;
+            field.default = Some(default);
+        }
+    }
+    Ok(())
+}
+
+// Errors carry the offending symbol and why
+fn enum_symbol(
+    enum_schema: &EnumSchema,
+    default: &str,
+    enclosing_namespace: &Namespace,
+    options: &ParseOptions,
+) -> Result<Value, (String, String)> {
+    let name = &enum_schema.name;
+    let fullname = name
+        .fully_qualified_name(enclosing_namespace)
+        .fullname(None);
+    let symbol = match default.rsplit_once('.') {
+        Some(_) if options.strict => {
+            return Err((
+                default.to_string(),
+                format!("Qualified enum symbol {default} is not allowed in strict mode"),
+            ));
+        }
+        Some((qualifier, _)) if qualifier != name.name && qualifier != fullname => {
+            return Err((
+                default.to_string(),
+                format!("Enum symbol {default} does not belong to {fullname}"),
+            ));
+        }
+        Some((_, symbol)) => symbol,
+        None => default,
+    };
+    if !enum_schema.symbols.iter().any(|s| s == symbol) {
+        let reason = unknown_symbol(enum_schema, symbol, enclosing_namespace);
+        return Err((default.to_string(), reason));
+    }
+    Ok(Value::String(symbol.to_string()))
+}
+
+fn unknown_symbol(
+    enum_schema: &EnumSchema,
+    symbol: &str,
+    enclosing_namespace: &Namespace,
+) -> String {
+    let fullname = enum_schema
+        .name
+        .fully_qualified_name(enclosing_namespace)
+        .fullname(None);
+    format!("Unknown symbol {symbol} for enum {fullname}")
+}
+
+// Check the size of the defaults of fixed fields, only known once the references
+// to the fixed are resolved.
+fn fixed_default_solver(
+    schema: &Schema,
+    enclosing_namespace: &Namespace,
+) -> Result<(), DefaultError> {
+    if let Schema::Record(RecordSchema { name, fields, .. }) = schema {
+        for field in fields.iter() {
+            fixed_default_solver(&field.schema, enclosing_namespace)?;
+            let (size, default) = match (&field.schema, &field.default) {
+                (Schema::Fixed(FixedSchema { size, .. }), Some(Value::String(default))) => {
+                    (*size, default)
+                }
+                _ => continue,
+            };
+            latin1_bytes(default)
+                .and_then(|bytes| check_fixed_size(bytes.len(), size))
+                .map_err(|e| {
+                    let symbol = format!("\"{default}\"");
+                    DefaultError::new(name, enclosing_namespace, field, (symbol, e))
+                })?;
+        }
+    }
+    Ok(())
+}
+
+// Records have no default literal in Avdl, a default of a record, or of a
+// collection of records, is only parsed as enum symbols or strings once the
+// reference is resolved. Only empty collections are accepted.
+fn record_default_solver(
+    schema: &Schema,
+    enclosing_namespace: &Namespace,
+) -> Result<(), DefaultError> {
+    if let Schema::Record(RecordSchema { name, fields, .. }) = schema {
+        for field in fields.iter() {
+            record_default_solver(&field.schema, enclosing_namespace)?;
+            let (offending, accepted) = match (&field.schema, &field.default) {
+                (Schema::Record(_), Some(default)) => (Some(default), ""),
+                (Schema::Array(items), Some(Value::Array(defaults))) => match items.as_ref() {
+                    Schema::Record(_) => (defaults.first(), ", only an empty array is accepted"),
+                    _ => continue,
+                },
+                (Schema::Map(values), Some(Value::Object(defaults))) => match values.as_ref() {
+                    Schema::Record(_) => {
+                        (defaults.values().next(), ", only an empty map is accepted")
+                    }
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if let Some(offending) = offending {
+                let offending = match offending {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                let reason = format!(
+                    "Invalid default for {}, records have no default literal in Avdl{accepted}",
+                    field.name
+                );
+                return Err(DefaultError::new(
+                    name,
+                    enclosing_namespace,
+                    field,
+                    (offending, reason),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn named_type_name(schema: &Schema, enclosing_namespace: &Namespace) -> Option<Name> {
+    match schema {
+        Schema::Record(record) => Some(record.name.fully_qualified_name(enclosing_namespace)),
+        Schema::Enum(EnumSchema { name, .. }) | Schema::Fixed(FixedSchema { name, .. }) => {
+            Some(name.fully_qualified_name(enclosing_namespace))
+        }
+        _ => None,
+    }
+}
+
+// Copy of `schema` where the named types found in `declared` are replaced by
+// references, the others are added to it. Used to write a named type in full
+// only where it first appears.
+pub(crate) fn reference_declared(
+    schema: &Schema,
+    declared: &mut Vec<Name>,
+    enclosing_namespace: &Namespace,
+) -> Schema {
+    if let Some(name) = named_type_name(schema, enclosing_namespace) {
+        if declared.contains(&name) {
+            return Schema::Ref { name };
+        }
+        declared.push(name);
+    }
+    match schema {
+        Schema::Record(record) => {
+            let namespace = record
+                .name
+                .fully_qualified_name(enclosing_namespace)
+                .namespace;
+            let mut record = record.clone();
+            for field in record.fields.iter_mut() {
+                field.schema = reference_declared(&field.schema, declared, &namespace);
+            }
+            Schema::Record(record)
+        }
+        Schema::Array(items) => Schema::Array(Box::new(reference_declared(
+            items,
+            declared,
+            enclosing_namespace,
+        ))),
+        Schema::Map(values) => Schema::Map(Box::new(reference_declared(
+            values,
+            declared,
+            enclosing_namespace,
+        ))),
+        Schema::Union(union_schema) => {
+            let variants = union_schema
+                .variants()
+                .iter()
+                .map(|variant| reference_declared(variant, declared, enclosing_namespace))
+                .collect();
+            // Named variants keep their name, so they stay distinct
+            Schema::Union(UnionSchema::new(variants).expect("Variants of a union are distinct"))
+        }
+        schema => schema.clone(),
+    }
+}
+
+fn namespace_solver(schema: &mut Schema, enclosing_namespace: &Namespace) -> () {
+    match schema {
+        Schema::Record(RecordSchema { name, .. })
+        | Schema::Enum(EnumSchema { name, .. })
+        | Schema::Fixed(FixedSchema { name, .. })
+            if name.namespace.is_none() =>
+        {
+            // A namespace given to the type itself wins over the enclosing one
+            name.namespace = enclosing_namespace.clone();
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use apache_avro::schema::{Alias, Name, RecordField, RecordFieldOrder, Schema};
+    use rstest::rstest;
+    use serde_json::{json, Map, Number, Value};
+
+    // The declaration parsers without where their parts are written
+    fn parse_enum(input: &str) -> IResult<&str, Schema> {
+        map(parse_enum_located, |(schema, _source)| schema)(input)
+    }
+
+    fn parse_fixed(input: &str) -> IResult<&str, Schema> {
+        map(parse_fixed_located, |(schema, _source)| schema)(input)
+    }
+
+    fn parse_record_field(input: &str) -> IResult<&str, RecordField> {
+        map(parse_record_field_located, |(field, _source)| field)(input)
+    }
+
+    fn parse_message(input: &str) -> IResult<&str, Message> {
+        map(parse_message_located, |(message, _source)| message)(input)
+    }
+
+    #[rstest]
+    #[case("// holis\n", " holis")]
+    #[case(
+        "// TODO: Move to another place, etc.\n",
+        " TODO: Move to another place, etc."
+    )]
+    #[case("/*Som343f */", "Som343f ")]
+    #[case("//Som343f\n", "Som343f")]
+    #[case("/* holis */", " holis ")]
+    #[case(
+        "/* TODO: Move to another place, etc. */",
+        " TODO: Move to another place, etc. "
+    )]
+    fn test_parse_comment_ok<'a>(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(parse_comment::<'a, &str, ()>(input), Ok(("", expected)));
     }
 
     #[rstest]
@@ -1169,6 +3331,18 @@ mod test {
         "/** Documentation for the enum type Kind */",
         "Documentation for the enum type Kind"
     )]
+    #[case(
+        "/** first line   \n   second line\t\n   last */",
+        "first line\nsecond line\nlast"
+    )]
+    #[case(
+        "/**\n     * First paragraph,\n     * on two lines.\n     *\n     *\n     * Second paragraph.\n     */",
+        "First paragraph,\non two lines.\n\nSecond paragraph."
+    )]
+    #[case(
+        "/** Sample:\n      fn main() {\n          run();\n      }\n\n      That's all.\n  */",
+        "Sample:\nfn main() {\n    run();\n}\n\nThat's all."
+    )]
     fn test_parse_doc(#[case] input: &str, #[case] expected: String) {
         assert_eq!(parse_doc(input), Ok(("", expected)))
     }
@@ -1198,6 +3372,8 @@ mod test {
     #[case(r#"@aliases ( [ "oldField", "ancientField" ] )"#, vec![String::from("oldField"), String::from("ancientField")])]
     #[case(r#"@aliases ( [ "oldField", /* holis */ "ancientField" ] )"#, vec![String::from("oldField"), String::from("ancientField")])]
     #[case("@aliases ( [ \"oldField\" // \"ancientField\" \n ] )", vec![String::from("oldField")])]
+    #[case("@aliases(\n  [\n    \"oldField\",\n    \"ancientField\"\n  ]\n)", vec![String::from("oldField"), String::from("ancientField")])]
+    #[case("@aliases /* a */ ( /* b */ [ \"oldField\" /* c */ /* d */ , // e\n \"ancientField\" ] /* f */ )", vec![String::from("oldField"), String::from("ancientField")])]
     fn test_alias(#[case] input: &str, #[case] expected: Vec<String>) {
         assert_eq!(parse_aliases(input), Ok(("", expected)));
     }
@@ -1206,6 +3382,7 @@ mod test {
     #[case(r#"@aliases(["oldField", "ancientField"])"#, vec![Alias::new("oldField").unwrap(), Alias::new("ancientField").unwrap()])]
     #[case(r#"@aliases(["oldField","ancientField"])"#, vec![Alias::new("oldField").unwrap(), Alias::new("ancientField").unwrap()])]
     #[case(r#"@aliases(["org.old.OldRecord","org.ancient.AncientRecord"])"#, vec![Alias::new("org.old.OldRecord").unwrap(), Alias::new("org.ancient.AncientRecord").unwrap()])]
+    #[case("@aliases( [ \"org.old.OldRecord\", /* was */\n \"org.ancient.AncientRecord\" ] )", vec![Alias::new("org.old.OldRecord").unwrap(), Alias::new("org.ancient.AncientRecord").unwrap()])]
     fn test_namespaced_alias(#[case] input: &str, #[case] expected: Vec<Alias>) {
         assert_eq!(parse_namespaced_aliases(input), Ok(("", expected)));
     }
@@ -1254,12 +3431,45 @@ mod test {
         assert_eq!(parse_namespace_value(input), Ok(("", expected)))
     }
 
+    #[rstest]
+    #[case(r#""org..foo""#)]
+    #[case(r#""1org.foo""#)]
+    #[case(r#""org.2foo""#)]
+    #[case(r#""org.foo.""#)]
+    #[case(r#"".org""#)]
+    fn test_namespace_parser_invalid(#[case] input: &str) {
+        match parse_namespace_value(input) {
+            Err(nom::Err::Failure(e)) => {
+                let namespace = input.trim_matches('"');
+                assert!(e
+                    .message
+                    .is_some_and(|m| m.starts_with(&format!("Invalid namespace {namespace},"))));
+            }
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_namespace_statement() {
+        let error = parse_schemas(
+            "namespace org..example;
+record R { int a; }",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.message,
+            "Invalid namespace org..example, each part must start with a letter or _ and contain only letters, digits and _"
+        );
+        assert_eq!((error.line, error.column), (1, 11));
+    }
+
     #[rstest]
     #[case("string message;", (Schema::String, None, None, None, "message",None))]
     #[case("string  message;", (Schema::String, None, None, None, "message",None))]
     #[case("string message ;", (Schema::String, None, None, None, "message",None))]
     #[case(r#"string message = "holis" ;"#, (Schema::String, None, None, None, "message",Some(Value::String("holis".into()))))]
     #[case(r#"string message = "holis";"#, (Schema::String, None, None, None, "message",Some(Value::String("holis".into()))))]
+    #[case(r#"string @aliases(["a"]) @aliases(["b"]) message;"#, (Schema::String, None, None, Some(vec![String::from("a"), String::from("b")]), "message",None))]
     #[case(r#"string @order("ignore") message = "holis";"#, (Schema::String, None, Some(RecordFieldOrder::Ignore), None, "message",Some(Value::String("holis".into()))))]
     #[case(r#"string @order("ignore") message = "holis how are you";"#, (Schema::String, None, Some(RecordFieldOrder::Ignore), None, "message",Some(Value::String("holis how are you".into()))))]
     fn test_parse_string_ok(
@@ -1290,9 +3500,9 @@ mod test {
     #[case("bytes message;", (Schema::Bytes, None, None, None, "message",None))]
     #[case("bytes  message;", (Schema::Bytes, None, None, None, "message",None))]
     #[case("bytes message ;", (Schema::Bytes, None, None, None, "message",None))]
-    #[case(r#"bytes message = "holis" ;"#, (Schema::Bytes, None, None, None, "message",Some(Value::Array(Vec::from([Value::Number(104.into()), Value::Number(111.into()), Value::Number(108.into()), Value::Number(105.into()), Value::Number(115.into())])))))]
-    #[case(r#"bytes message = "holis";"#, (Schema::Bytes, None, None, None, "message",Some(Value::Array(Vec::from([Value::Number(104.into()), Value::Number(111.into()), Value::Number(108.into()), Value::Number(105.into()), Value::Number(115.into())])))))]
-    #[case(r#"bytes @order("ignore") message = "holis";"#, (Schema::Bytes, None, Some(RecordFieldOrder::Ignore), None, "message",Some(Value::Array(Vec::from([Value::Number(104.into()), Value::Number(111.into()), Value::Number(108.into()), Value::Number(105.into()), Value::Number(115.into())])))))]
+    #[case(r#"bytes message = "holis" ;"#, (Schema::Bytes, None, None, None, "message",Some(Value::from("holis"))))]
+    #[case(r#"bytes message = "holis";"#, (Schema::Bytes, None, None, None, "message",Some(Value::from("holis"))))]
+    #[case(r#"bytes @order("ignore") message = "holis";"#, (Schema::Bytes, None, Some(RecordFieldOrder::Ignore), None, "message",Some(Value::from("holis"))))]
     fn test_parse_bytes_ok(
         #[case] input: &str,
         #[case] expected: (
@@ -1364,7 +3574,8 @@ mod test {
     }
 
     #[rstest]
-    #[case("decimal(1,2) age = \"1.2\";", (Schema::Decimal(DecimalSchema { precision: 1, scale: 2, inner: Box::new(Schema::Bytes) }), None, None, None, "age", Some(AvroValue::Decimal("1.2".into()).try_into().unwrap())))]
+    #[case("decimal(3,2) age = \"1.2\";", (Schema::Decimal(DecimalSchema { precision: 3, scale: 2, inner: Box::new(Schema::Bytes) }), None, None, None, "age", Some(Value::from("x"))))]
+    #[case("decimal ( 5 , 2 ) price;", (Schema::Decimal(DecimalSchema { precision: 5, scale: 2, inner: Box::new(Schema::Bytes) }), None, None, None, "price", None))]
     #[case("int age;", (Schema::Int, None, None, None, "age", None))]
     #[case("/** How old is */ int age;", (Schema::Int, Some(String::from("How old is")), None, None, "age", None))]
     #[case("int age = 12;", (Schema::Int, None, None, None, "age", Some(Value::Number(12.into()))))]
@@ -1376,6 +3587,10 @@ mod test {
     #[case("time_ms   age   =   123 ;", (Schema::TimeMillis, None, None, None, "age", Some(Value::Number(123.into()))))]
     #[case("timestamp_ms age;", (Schema::TimestampMillis, None, None, None, "age", None))]
     #[case("timestamp_ms age = 12;", (Schema::TimestampMillis, None, None, None, "age", Some(Value::Number(12.into()))))]
+    #[case("time_us age;", (Schema::TimeMicros, None, None, None, "age", None))]
+    #[case("time_us age = 12;", (Schema::TimeMicros, None, None, None, "age", Some(Value::Number(12.into()))))]
+    #[case("timestamp_us age;", (Schema::TimestampMicros, None, None, None, "age", None))]
+    #[case("timestamp_us age = 1700000000000000;", (Schema::TimestampMicros, None, None, None, "age", Some(Value::Number(1700000000000000i64.into()))))]
     #[case("@logicalType(\"timestamp-micros\")\nlong ts = 12;", (Schema::TimestampMicros, None, None, None, "ts", Some(Value::Number(12.into()))))]
     #[case("date age;", (Schema::Date, None, None, None, "age", None))]
     #[case("date age = 12;", (Schema::Date, None, None, None, "age", Some(Value::Number(12.into()))))]
@@ -1439,6 +3654,9 @@ mod test {
     #[case("float age = 3.4028234663852886e38;", (Schema::Float, None, None, None, "age", Some(Value::Number(Number::from_f64(f32::MAX.into()).unwrap()))))]
     #[case("float age = 0;", (Schema::Float, None, None, None, "age", Some(Value::Number(Number::from_f64(0.0).unwrap()))))]
     #[case("float   age   =   123 ;", (Schema::Float, None, None, None, "age", Some(Value::Number(Number::from_f64(123.0).unwrap()))))]
+    #[case("float x = -.5e-3;", (Schema::Float, None, None, None, "x", Some(Value::Number(Number::from_f64(-0.0005).unwrap()))))]
+    #[case("float x = 1.5E2;", (Schema::Float, None, None, None, "x", Some(Value::Number(Number::from_f64(150.0).unwrap()))))]
+    #[case("float x = 1.5e2;", (Schema::Float, None, None, None, "x", Some(Value::Number(Number::from_f64(150.0).unwrap()))))]
     fn test_parse_float_ok(
         #[case] input: &str,
         #[case] expected: (
@@ -1458,6 +3676,7 @@ mod test {
     #[case(r#"float age = "false""#)] // wrong type
     #[case(r#"float age = 123"#)] // missing semi-colon with default
     #[case("float age = 3.50282347e40;")] // longer than f32
+    #[case("float age = -.5e+40;")] // longer than f32
     fn test_parse_float_fail(#[case] input: &str) {
         let res = parse_field(input);
         assert!(res.is_err());
@@ -1471,6 +3690,10 @@ mod test {
     #[case("double stock = 1.7976931348623157e308;", (Schema::Double, None, None, None, "stock", Some(Value::Number(Number::from_f64(f64::MAX).unwrap()))))]
     #[case("double stock = 0.0;", (Schema::Double, None, None, None, "stock", Some(Value::Number(Number::from_f64(0.0).unwrap()))))]
     #[case("double stock = .0;", (Schema::Double, None, None, None, "stock", Some(Value::Number(Number::from_f64(0.0).unwrap()))))]
+    #[case("double y = -.5E+3;", (Schema::Double, None, None, None, "y", Some(Value::Number(Number::from_f64(-500.0).unwrap()))))]
+    #[case("double y = 1.5E2;", (Schema::Double, None, None, None, "y", Some(Value::Number(Number::from_f64(150.0).unwrap()))))]
+    #[case("double y = 1.5e2;", (Schema::Double, None, None, None, "y", Some(Value::Number(Number::from_f64(150.0).unwrap()))))]
+    #[case("double y = 1.0E3;", (Schema::Double, None, None, None, "y", Some(Value::Number(Number::from_f64(1000.0).unwrap()))))]
     #[case("double stock = 0;", (Schema::Double, None, None, None, "stock", Some(Value::Number(Number::from_f64(0.0).unwrap()))))]
     #[case(r#"double @order("descending") stock = 0;"#, (Schema::Double, None, Some(RecordFieldOrder::Descending), None, "stock", Some(Value::Number(Number::from_f64(0.0).unwrap()))))]
     #[case("double   stock   =   123.3 ;", (Schema::Double, None, None, None, "stock", Some(Value::Number(Number::from_f64(123.3).unwrap()))))]
@@ -1506,6 +3729,12 @@ mod test {
     #[case(r#"array<string> stock = ["cacao nibs"];"#, (Schema::Array(Box::new(Schema::String)), None, None, None, "stock", Some(Value::Array(Vec::from([Value::String(String::from("cacao nibs"))])))))]
     #[case(r#"array<string> @aliases(["item"]) stock;"#, (Schema::Array(Box::new(Schema::String)), None, None, Some(vec![String::from("item")]), "stock", None))]
     #[case(r#"array<string> @order("ascending") stock;"#, (Schema::Array(Box::new(Schema::String)), None, Some(RecordFieldOrder::Ascending), None, "stock", None))]
+    #[case(r#"array<map<int>> xs = [{"a": 1}];"#, (Schema::Array(Box::new(Schema::Map(Box::new(Schema::Int)))), None, None, None, "xs", Some(serde_json::json!([{"a": 1}]))))]
+    #[case(r#"array<decimal(5,2)> prices = ["1.23", "4.56"];"#, (Schema::Array(Box::new(Schema::Decimal(DecimalSchema { precision: 5, scale: 2, inner: Box::new(Schema::Bytes) }))), None, None, None, "prices", Some(json!(["{", "\u{1}\u{c8}"]))))]
+    #[case("array < int > xs;", (Schema::Array(Box::new(Schema::Int)), None, None, None, "xs", None))]
+    #[case("array <map< /* counts */ long >> xs;", (Schema::Array(Box::new(Schema::Map(Box::new(Schema::Long)))), None, None, None, "xs", None))]
+    #[case("array< Item > items;", (Schema::Array(Box::new(Schema::Ref { name: Name::new("Item").unwrap() })), None, None, None, "items", None))]
+    #[case(r#"array<int> @aliases(["old"]) @order("ignore") xs;"#, (Schema::Array(Box::new(Schema::Int)), None, Some(RecordFieldOrder::Ignore), Some(vec![String::from("old")]), "xs", None))]
     fn test_parse_array_ok(
         #[case] input: &str,
         #[case] expected: (
@@ -1524,6 +3753,13 @@ mod test {
     #[case(r#"map<string> stock;"#, (Schema::Map(Box::new(Schema::String)), None, None, None, "stock", None))]
     #[case(r#"map<string> @order("ascending") stock;"#, (Schema::Map(Box::new(Schema::String)), None, Some(RecordFieldOrder::Ascending), None, "stock", None))]
     #[case(r#"map<string> stock = {"hey": "hello"};"#, (Schema::Map(Box::new(Schema::String)), None, None, None, "stock", Some(Value::Object(Map::from_iter([(String::from("hey"), Value::String(String::from("hello")))])))))]
+    #[case(r#"map<int> stock = {"key\twith\ttabs": 1};"#, (Schema::Map(Box::new(Schema::Int)), None, None, None, "stock", Some(Value::Object(Map::from_iter([(String::from("key\twith\ttabs"), Value::Number(1.into()))])))))]
+    #[case(r#"map<int> stock = {"\"quoted\" \u{e9}": 1};"#, (Schema::Map(Box::new(Schema::Int)), None, None, None, "stock", Some(Value::Object(Map::from_iter([(String::from("\"quoted\" é"), Value::Number(1.into()))])))))]
+    #[case(r#"map<map<int>> nested = {"a": {"b": 1}, "c": {}};"#, (Schema::Map(Box::new(Schema::Map(Box::new(Schema::Int)))), None, None, None, "nested", Some(serde_json::json!({"a": {"b": 1}, "c": {}}))))]
+    #[case("map < string > stock;", (Schema::Map(Box::new(Schema::String)), None, None, None, "stock", None))]
+    #[case("map <array < int >> stock;", (Schema::Map(Box::new(Schema::Array(Box::new(Schema::Int)))), None, None, None, "stock", None))]
+    #[case(r#"map<int> @order("descending") @aliases(["old"]) counts;"#, (Schema::Map(Box::new(Schema::Int)), None, Some(RecordFieldOrder::Descending), Some(vec![String::from("old")]), "counts", None))]
+    #[case(r#"map<int> @aliases(["old"]) @order("descending") counts = {};"#, (Schema::Map(Box::new(Schema::Int)), None, Some(RecordFieldOrder::Descending), Some(vec![String::from("old")]), "counts", Some(serde_json::json!({}))))]
     fn test_parse_map_ok(
         #[case] input: &str,
         #[case] expected: (
@@ -1538,6 +3774,271 @@ mod test {
         assert_eq!(parse_map(input), Ok(("", expected)));
     }
 
+    #[rstest]
+    #[case("int x = 1.0;", "x", "an int can't be fractional")]
+    #[case("long y = 12.5;", "y", "a long can't be fractional")]
+    fn test_parse_fractional_integral_default(
+        #[case] field: &str,
+        #[case] name: &str,
+        #[case] reason: &str,
+    ) {
+        let input = format!("protocol P {{ record R {{ {field} }} }}");
+        let error = parse_schemas(&input).unwrap_err();
+
+        let expected = ParseErrorKind::InvalidDefault {
+            field: name.to_string(),
+            reason: reason.to_string(),
+        };
+        assert_eq!(error.kind, expected);
+        assert_eq!(error.offset, input.find('.').unwrap());
+    }
+
+    #[rstest]
+    #[case("boolean b = 1;", "b", "expected a boolean, found 1")]
+    #[case("string s = true;", "s", "expected a string, found true")]
+    #[case(r#"int i = "one";"#, "i", r#"expected an int, found "one""#)]
+    #[case("double d = false;", "d", "expected a double, found false")]
+    #[case("array<int> a = [1, true];", "a", "expected an int, found true")]
+    #[case("string s = null;", "s", "expected a string, found null")]
+    #[case(
+        "union { string, null } s = null;",
+        "s",
+        "expected a string, found null"
+    )]
+    fn test_parse_mismatched_default(
+        #[case] field: &str,
+        #[case] name: &str,
+        #[case] reason: &str,
+    ) {
+        let input = format!("protocol P {{ record R {{ {field} }} }}");
+        let error = parse_schemas(&input).unwrap_err();
+
+        let expected = ParseErrorKind::InvalidDefault {
+            field: name.to_string(),
+            reason: reason.to_string(),
+        };
+        assert_eq!(error.kind, expected);
+    }
+
+    #[rstest]
+    #[case(Schema::Boolean, json!(true))]
+    #[case(Schema::Long, json!(-3))]
+    #[case(Schema::Double, json!(1))]
+    #[case(Schema::Array(Box::new(Schema::String)), json!(["a"]))]
+    #[case(Schema::Union(UnionSchema::new(vec![Schema::Int, Schema::Null]).unwrap()), json!(2))]
+    fn test_validate_default_ok(#[case] schema: Schema, #[case] value: Value) {
+        assert_eq!(validate_default(&schema, &value), Ok(()));
+    }
+
+    #[rstest]
+    #[case(r#""12.34""#, vec![0x04, 0xD2])]
+    #[case("12.34", vec![0x04, 0xD2])]
+    #[case("-12.34", vec![0xFB, 0x2E])]
+    #[case("7", vec![0x02, 0xBC])]
+    #[case(r#""0.5""#, vec![0x32])]
+    fn test_parse_decimal_default(#[case] input: &str, #[case] expected: Vec<u8>) {
+        let schema = Schema::Decimal(DecimalSchema {
+            precision: 4,
+            scale: 2,
+            inner: Box::new(Schema::Bytes),
+        });
+        assert_eq!(
+            parse_based_on_schema(Box::new(schema))(input),
+            Ok(("", AvroValue::Decimal(expected.into())))
+        );
+    }
+
+    #[rstest]
+    #[case("decimal(5,2)", Ok(("", Schema::Decimal(DecimalSchema { precision: 5, scale: 2, inner: Box::new(Schema::Bytes) }))))]
+    #[case("decimal(3,3)", Ok(("", Schema::Decimal(DecimalSchema { precision: 3, scale: 3, inner: Box::new(Schema::Bytes) }))))]
+    #[case(
+        "decimal(2,5)",
+        Err(String::from("Decimal scale 5 is greater than its precision 2"))
+    )]
+    fn test_map_decimal_type(
+        #[case] input: &str,
+        #[case] expected: Result<(&str, Schema), String>,
+    ) {
+        let res = map_type_to_schema(input).map_err(|e| match e {
+            nom::Err::Failure(e) => e.message.unwrap_or_default(),
+            e => format!("expected a failure, got {e:?}"),
+        });
+        assert_eq!(res, expected);
+    }
+
+    #[rstest]
+    #[case("1.2", 2, "1.20")]
+    #[case("-0.01", 2, "-0.01")]
+    #[case("32767", 0, "32767")]
+    #[case("-32768", 0, "-32768")]
+    #[case("128", 1, "128.0")]
+    #[case("-9999999.999", 3, "-9999999.999")]
+    fn test_decimal_bytes_round_trip(
+        #[case] value: &str,
+        #[case] scale: usize,
+        #[case] expected: &str,
+    ) {
+        let bytes = decimal_bytes(unscaled_decimal(value, 38, scale).unwrap());
+
+        // Big-endian two's-complement, sign extended from the first byte
+        let first = bytes[0] as i8 as i128;
+        let unscaled = bytes[1..]
+            .iter()
+            .fold(first, |acc, b| (acc << 8) | *b as i128);
+        let digits = format!("{:0>width$}", unscaled.unsigned_abs(), width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        let sign = if unscaled < 0 { "-" } else { "" };
+        let decoded = if scale == 0 {
+            format!("{sign}{integer}")
+        } else {
+            format!("{sign}{integer}.{fraction}")
+        };
+        assert_eq!(decoded, expected);
+    }
+
+    #[rstest]
+    #[case("123.4", "123.4 has more than 4 digits")]
+    #[case(r#""-100""#, "-100 has more than 4 digits")]
+    #[case("1.234", "1.234 has more than 2 fractional digits")]
+    fn test_unscaled_decimal_error(#[case] input: &str, #[case] message: &str) {
+        let value = input.trim_matches('"');
+        assert_eq!(unscaled_decimal(value, 4, 2), Err(message.to_string()));
+        match parse_field(&format!("decimal(4,2) price = {input};")) {
+            Err(nom::Err::Failure(e)) => assert_eq!(e.message.as_deref(), Some(message)),
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case(Schema::Int, "5", AvroValue::Int(5))]
+    #[case(Schema::Long, "5", AvroValue::Long(5))]
+    #[case(Schema::Float, "5", AvroValue::Float(5.0))]
+    #[case(Schema::Float, "-2.5", AvroValue::Float(-2.5))]
+    #[case(Schema::Double, "5", AvroValue::Double(5.0))]
+    #[case(Schema::Double, "-2.5", AvroValue::Double(-2.5))]
+    fn test_parse_numeric_default_variant(
+        #[case] schema: Schema,
+        #[case] input: &str,
+        #[case] expected: AvroValue,
+    ) {
+        assert_eq!(
+            parse_based_on_schema(Box::new(schema))(input),
+            Ok(("", expected))
+        );
+    }
+
+    #[test]
+    fn test_parse_float_array_default() {
+        let (_tail, (_, _, _, _, _, default)) = parse_array("array<float> xs = [0.1, 2];").unwrap();
+        assert_eq!(default, Some(json!([0.1, 2.0])));
+    }
+
+    #[test]
+    fn test_parse_fixed_default() {
+        let schema = Box::new(Schema::Fixed(FixedSchema {
+            name: "Tag".into(),
+            aliases: None,
+            doc: None,
+            size: 4,
+            attributes: BTreeMap::new(),
+        }));
+        let (_tail, value) = parse_based_on_schema(schema.clone())(r#""abcd""#).unwrap();
+        assert_eq!(value, AvroValue::Fixed(4, b"abcd".to_vec()));
+
+        match parse_based_on_schema(schema)(r#""abc""#) {
+            Err(nom::Err::Failure(e)) => assert_eq!(
+                e.message.as_deref(),
+                Some("A fixed of size 4 takes a default of 4 bytes, found 3")
+            ),
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case(r#""abcd""#, Ok(AvroValue::Fixed(4, b"abcd".to_vec())))]
+    #[case(r#""abcÿ""#, Ok(AvroValue::Fixed(4, b"abc\xff".to_vec())))]
+    #[case(r#""abc€""#, Err("€ is not a byte, only \\u0000 to \\u00ff are"))]
+    #[case(r#""""#, Err("A fixed of size 4 takes a default of 4 bytes, found 0"))]
+    #[case(
+        r#""ab""#,
+        Err("A fixed of size 4 takes a default of 4 bytes, found 2")
+    )]
+    #[case(
+        r#""abcdefgh""#,
+        Err("A fixed of size 4 takes a default of 4 bytes, found 8")
+    )]
+    fn test_map_fixed_size(#[case] input: &str, #[case] expected: Result<AvroValue, &str>) {
+        let res = map_fixed(input, 4).map(|(_tail, value)| value);
+        match (res, expected) {
+            (Ok(value), Ok(expected)) => assert_eq!(value, expected),
+            (Err(nom::Err::Failure(e)), Err(expected)) => {
+                assert_eq!(e.message.as_deref(), Some(expected))
+            }
+            (res, expected) => panic!("expected {expected:?}, got {res:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case("array<R> xs = [X];", "Invalid default for xs, records have no default literal in Avdl, only an empty array is accepted")]
+    #[case(r#"map<R> xs = {"a": X};"#, "Invalid default for xs, records have no default literal in Avdl, only an empty map is accepted")]
+    #[case(
+        "R r = X;",
+        "Invalid default for r, records have no default literal in Avdl"
+    )]
+    fn test_parse_record_default_error(#[case] field: &str, #[case] expected: &str) {
+        let input = format!("protocol P {{ record R {{ int a; }} record S {{ {field} }} }}");
+        let error = parse_schemas(&input).unwrap_err();
+
+        assert_eq!(error.message, expected);
+        assert_eq!(error.offset, input.find('X').unwrap());
+    }
+
+    #[rstest]
+    #[case("array<R> xs = [];", json!([]))]
+    #[case("map<R> xs = {};", json!({}))]
+    fn test_parse_empty_record_collection_default(#[case] field: &str, #[case] expected: Value) {
+        let input = format!("protocol P {{ record R {{ int a; }} record S {{ {field} }} }}");
+        let schemas = parse_schemas(&input).unwrap();
+        let Schema::Record(record) = &schemas[1] else {
+            panic!("expected a record, got {:?}", schemas[1]);
+        };
+        assert_eq!(record.fields[0].default, Some(expected));
+    }
+
+    #[test]
+    fn test_parse_fixed_field_default() {
+        let input = r#"protocol P {
+            fixed Tag(4);
+            record R { Tag tag = "abcd"; }
+        }"#;
+        let schemas = parse_schemas(input).unwrap();
+        let Schema::Record(record) = &schemas[1] else {
+            panic!("expected a record, got {:?}", schemas[1]);
+        };
+        assert_eq!(record.fields[0].default, Some(json!("abcd")));
+
+        let input = r#"protocol P {
+            fixed Tag(4);
+            record R { Tag tag = "abcdef"; }
+        }"#;
+        let error = parse_schemas(input).unwrap_err();
+        assert_eq!(
+            error.message,
+            "A fixed of size 4 takes a default of 4 bytes, found 6"
+        );
+        assert_eq!((error.line, error.column), (3, 34));
+    }
+
+    #[test]
+    fn test_parse_map_default_order() {
+        let input = r#"map<int> counts = {"b": 2, "c": 3, "a": 1, "d": 4};"#;
+        for _ in 0..10 {
+            let (_tail, (_, _, _, _, _, default)) = parse_map(input).unwrap();
+            let out = serde_json::to_string(&default).unwrap();
+            assert_eq!(out, r#"{"a":1,"b":2,"c":3,"d":4}"#);
+        }
+    }
+
     #[rstest]
     #[case(
         r#"union { null, string } item_id = null;"#, (Schema::Union(UnionSchema::new(vec![Schema::Null, Schema::String]).unwrap()), None, None, None, "item_id", Some(Value::Null))
@@ -1554,6 +4055,21 @@ mod test {
     #[case(
         r#"union { string, int } item = "1";"#, (Schema::Union(UnionSchema::new(vec![Schema::String, Schema::Int]).unwrap()), None, None, None, "item", Some(Value::String("1".to_string())))
     )]
+    #[case(
+        r#"union { string, int } x = "a";"#, (Schema::Union(UnionSchema::new(vec![Schema::String, Schema::Int]).unwrap()), None, None, None, "x", Some(Value::String("a".to_string())))
+    )]
+    #[case(
+        r#"union { array<int>, null } x = [1, 2];"#, (Schema::Union(UnionSchema::new(vec![Schema::Array(Box::new(Schema::Int)), Schema::Null]).unwrap()), None, None, None, "x", Some(Value::Array(vec![Value::Number(1.into()), Value::Number(2.into())])))
+    )]
+    #[case(
+        r#"union { null, string } @order("ignore") @aliases(["x"]) f;"#, (Schema::Union(UnionSchema::new(vec![Schema::Null, Schema::String]).unwrap()), None, Some(RecordFieldOrder::Ignore), Some(vec![String::from("x")]), "f", None)
+    )]
+    #[case(
+        r#"union { null, string } @aliases(["x"]) @order("ignore") f = null;"#, (Schema::Union(UnionSchema::new(vec![Schema::Null, Schema::String]).unwrap()), None, Some(RecordFieldOrder::Ignore), Some(vec![String::from("x")]), "f", Some(Value::Null))
+    )]
+    #[case(
+        "union { /* first */ null /* none */, // a line\n int /* an int */ , array<int> /* last */ } x;", (Schema::Union(UnionSchema::new(vec![Schema::Null, Schema::Int, Schema::Array(Box::new(Schema::Int))]).unwrap()), None, None, None, "x", None)
+    )]
     fn test_union(
         #[case] input: &str,
         #[case] expected: (
@@ -1568,10 +4084,81 @@ mod test {
         assert_eq!(parse_union(input), Ok(("", expected)));
     }
 
+    #[rstest]
+    #[case("union { int, int } x;", "Duplicate type int in union")]
+    #[case("union { string, null, string } x;", "Duplicate type string in union")]
+    #[case("union { null, Thing, Thing } x;", "Duplicate type Thing in union")]
+    #[case("union { } x;", "A union needs at least one type")]
+    #[case("union {/* nothing */} x;", "A union needs at least one type")]
+    fn test_union_duplicate_type(#[case] input: &str, #[case] expected: &str) {
+        match parse_union(input) {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.input, input);
+                assert_eq!(e.message.as_deref(), Some(expected));
+            }
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_union_variant_order() {
+        let input = r#"protocol P {
+            record R { string s; }
+            record S {
+                union { string, int, null } x;
+                union { string, R, int, null } y;
+            }
+        }"#;
+        let schemas = parse_schemas(input).unwrap();
+        let Schema::Record(RecordSchema { fields, .. }) = &schemas[1] else {
+            panic!("expected a record, got {:?}", schemas[1]);
+        };
+
+        let expected = UnionSchema::new(vec![Schema::String, Schema::Int, Schema::Null]).unwrap();
+        assert_eq!(fields[0].schema, Schema::Union(expected));
+        let expected = UnionSchema::new(vec![
+            Schema::String,
+            schemas[0].clone(),
+            Schema::Int,
+            Schema::Null,
+        ])
+        .unwrap();
+        assert_eq!(fields[1].schema, Schema::Union(expected));
+    }
+
+    #[rstest]
+    #[case(
+        "union { int, string } x = true;",
+        "true;",
+        "Invalid default for x, expected an int, found true"
+    )]
+    #[case(
+        r#"union { int, string } x = "a";"#,
+        r#""a";"#,
+        r#"Invalid default for x, expected an int, found "a""#
+    )]
+    #[case(
+        "union { int, string } x = A;",
+        "= A;",
+        "Invalid default for x, it doesn't match the first of its types"
+    )]
+    fn test_union_invalid_default(#[case] input: &str, #[case] at: &str, #[case] message: &str) {
+        match parse_union(input) {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.input, at);
+                assert_eq!(e.message.as_deref(), Some(message));
+            }
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
     #[rstest]
     #[case(r#"fixed MD5(16);"#, Schema::Fixed(FixedSchema { name: "MD5".into(), aliases: None, doc: None, size: 16, attributes: BTreeMap::new()}))]
     #[case("/** my hash */ \nfixed MD5(16);", Schema::Fixed(FixedSchema { name: "MD5".into(), aliases: None, doc: Some("my hash".to_string()), size: 16, attributes: BTreeMap::new()}))]
     #[case(r#"fixed @aliases(["md1"]) MD5(16);"#, Schema::Fixed(FixedSchema { name: "MD5".into(), aliases: None, doc: None, size: 16, attributes: BTreeMap::new()}))]
+    #[case("fixed MD5 ( 16 );", Schema::Fixed(FixedSchema { name: "MD5".into(), aliases: None, doc: None, size: 16, attributes: BTreeMap::new()}))]
+    #[case("fixed MD5( /* bytes */ 16\n);", Schema::Fixed(FixedSchema { name: "MD5".into(), aliases: None, doc: None, size: 16, attributes: BTreeMap::new()}))]
+    #[case(r#"@namespace("org.other") fixed MD5(16);"#, Schema::Fixed(FixedSchema { name: "org.other.MD5".into(), aliases: None, doc: None, size: 16, attributes: BTreeMap::new()}))]
     fn test_parse_fixed_ok(#[case] input: &str, #[case] expected: Schema) {
         assert_eq!(parse_fixed(input), Ok(("", expected)));
     }
@@ -1580,6 +4167,9 @@ mod test {
     #[case(r#"= holis;"#, "holis")]
     #[case(r#"= holis ;"#, "holis")]
     #[case(r#"= CIRCLE;"#, "CIRCLE")]
+    #[case(r#" /* default */ = holis;"#, "holis")]
+    #[case(r#"= /* first */ holis /* done */ ;"#, "holis")]
+    #[case("// the default\n= holis\n;", "holis")]
     fn test_parse_enum_default(#[case] input: &str, #[case] expected: &str) {
         assert_eq!(parse_enum_default(input), Ok(("", expected.to_string())))
     }
@@ -1599,10 +4189,35 @@ mod test {
     #[case("{ SQUARE,TRIANGLE,CIRCLE,OVAL}")]
     #[case("{SQUARE,TRIANGLE,CIRCLE,OVAL}")]
     fn test_enum_body(#[case] input: &str) {
-        let expected = vec!["SQUARE", "TRIANGLE", "CIRCLE", "OVAL"];
+        let expected = vec![
+            (None, "SQUARE"),
+            (None, "TRIANGLE"),
+            (None, "CIRCLE"),
+            (None, "OVAL"),
+        ];
         assert_eq!(parse_enum_symbols(input), Ok(("", expected)))
     }
 
+    #[test]
+    fn test_parse_enum_symbol_docs() {
+        let input = "enum Color { /** red */ RED, /** green */ GREEN, BLUE }";
+        let (_tail, schema) = parse_enum(input).unwrap();
+        match schema {
+            Schema::Enum(EnumSchema {
+                symbols,
+                attributes,
+                ..
+            }) => {
+                assert_eq!(symbols, vec!["RED", "GREEN", "BLUE"]);
+                assert_eq!(
+                    attributes.get("symbolDocs"),
+                    Some(&serde_json::json!({"RED": "red", "GREEN": "green"}))
+                );
+            }
+            other => panic!("expected an enum, got {other:?}"),
+        }
+    }
+
     #[test]
     fn test_parse_enum() {
         let input = "enum Shapes {
@@ -1651,6 +4266,35 @@ mod test {
         assert_eq!(o, Ok(("", expected)));
     }
 
+    #[rstest]
+    #[case("enum E { 1X }", "1X")]
+    #[case("enum E { A, 2B, C }", "2B")]
+    #[case("enum E { A, B-C }", "B-C")]
+    fn test_parse_enum_invalid_symbol(#[case] input: &str, #[case] symbol: &str) {
+        match parse_enum(input) {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.input, &input[input.find(symbol).unwrap()..]);
+                assert_eq!(
+                    e.message,
+                    Some(format!("Invalid enum symbol {symbol}, it must start with a letter or _ and contain only letters, digits and _"))
+                );
+            }
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_enum_commented_default() {
+        let input = "protocol P { enum E { A, B } /* default */ = /* the second */ B; }";
+        let schemas = parse_schemas(input).unwrap();
+        match &schemas[0] {
+            Schema::Enum(EnumSchema { default, .. }) => {
+                assert_eq!(default.as_deref(), Some("B"))
+            }
+            other => panic!("expected an enum, got {other:?}"),
+        }
+    }
+
     #[test]
     fn test_parse_enum_with_alias_and_default() {
         let input = r#"@aliases(["org.old.OldRecord", "org.ancient.AncientRecord"])
@@ -1678,10 +4322,11 @@ mod test {
     }
 
     #[rstest]
-    #[case("record Hello", "Hello")]
-    #[case("record   OneTwo  ", "OneTwo")]
-    fn test_parse_record_name(#[case] input: &str, #[case] expected: &str) {
-        assert_eq!(parse_record_name(input), Ok(("", expected)))
+    #[case("record Hello", "record", "Hello")]
+    #[case("record   OneTwo  ", "record", "OneTwo")]
+    #[case("error NotFound", "error", "NotFound")]
+    fn test_parse_record_name(#[case] input: &str, #[case] keyword: &str, #[case] expected: &str) {
+        assert_eq!(parse_record_name(input, keyword), Ok(("", expected)))
     }
 
     #[rstest]
@@ -1729,6 +4374,61 @@ mod test {
         assert_eq!(canonical_form, expected)
     }
 
+    #[rstest]
+    #[case("double x = 5;", "5.0", true)]
+    #[case("double x = 5.0;", "5.0", false)]
+    #[case("float x = 5;", "5.0", true)]
+    #[case("float x = 5.0;", "5.0", false)]
+    #[case("union { double, null } x = 5;", "5.0", true)]
+    #[case("array<float> x = [5];", "[5.0]", false)]
+    #[case("long x = 5;", "5", false)]
+    fn test_parse_integral_literal_default(
+        #[case] field: &str,
+        #[case] default: &str,
+        #[case] integral: bool,
+    ) {
+        let input = format!("@namespace(\"org.x\") protocol P {{ record R {{ {field} }} }}");
+        let protocol = parse_idl(&input).unwrap();
+
+        let Schema::Record(RecordSchema { name, fields, .. }) = &protocol.types[0] else {
+            panic!("expected a record, got {:?}", protocol.types[0]);
+        };
+        let emitted = serde_json::to_string(fields[0].default.as_ref().unwrap()).unwrap();
+        assert_eq!(emitted, default);
+        assert_eq!(protocol.is_integral_default(name, "x"), integral);
+    }
+
+    #[test]
+    fn test_parse_record_null_defaults() {
+        let sample = r#"record Nullable {
+            null nothing = null;
+            union { null, string } maybe = null;
+        }"#;
+        let (_tail, schema) = parse_record(sample).unwrap();
+        let json = serde_json::to_value(&schema).unwrap();
+        let fields = json["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 2);
+        for field in fields {
+            assert_eq!(field.get("default"), Some(&Value::Null));
+        }
+    }
+
+    #[test]
+    fn test_parse_record_duplicate_field_position() {
+        let input = r#"record Hello {
+            string name;
+            int name;
+        }"#;
+        match parse_record(input) {
+            Err(nom::Err::Failure(e)) => {
+                let offset = input.len() - e.input.len();
+                assert_eq!(offset, input.find("int name").unwrap());
+                assert_eq!(e.message, Some(String::from("Duplicate field name")));
+            }
+            other => panic!("expected a duplicate field failure, got {other:?}"),
+        }
+    }
+
     #[test]
     fn test_parse_record_alias() {
         let sample = r#"@aliases(["org.old.OldRecord", "org.ancient.AncientRecord"])
@@ -1818,7 +4518,7 @@ mod test {
     )]
     fn test_parse_protocol(#[case] input: &str) {
         let mut names_ref = HashMap::new();
-        let r = parse_protocol(input, &mut names_ref).unwrap();
+        let r = parse_protocol(input, &mut names_ref, &ParseOptions::default()).unwrap();
         println!("{r:#?}");
     }
 
@@ -1833,7 +4533,7 @@ mod test {
     )]
     fn test_parse_protocol_duplicate_error(#[case] input: &str) {
         let mut names_ref = HashMap::new();
-        let r = parse_protocol(input, &mut names_ref);
+        let r = parse_protocol(input, &mut names_ref, &ParseOptions::default());
         // TODO: How to get proper error message?
         assert!(r.is_err());
     }
@@ -1917,6 +4617,1441 @@ mod test {
         assert_eq!(expected, schemas)
     }
 
+    #[test]
+    fn test_parse_protocol_with_array_of_record() {
+        let input = r#"protocol MyProtocol {
+            record Hello {
+                string name;
+            }
+            record Parent {
+                array<Hello> items;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+
+        let hello = schemas[0].clone();
+        match &schemas[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                assert_eq!(fields[0].schema, Schema::Array(Box::new(hello)));
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_protocol_with_map_of_record() {
+        let input = r#"protocol MyProtocol {
+            record Hello {
+                string name;
+            }
+            record Parent {
+                map<Hello> by_id;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+
+        let hello = schemas[0].clone();
+        match &schemas[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                assert_eq!(fields[0].schema, Schema::Map(Box::new(hello)));
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_protocol_with_nullable_record() {
+        let input = r#"protocol MyProtocol {
+            record Hello {
+                string name;
+            }
+            record Parent {
+                union { null, Hello } maybe = null;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+
+        let hello = schemas[0].clone();
+        match &schemas[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                let expected = UnionSchema::new(vec![Schema::Null, hello]).unwrap();
+                assert_eq!(fields[0].schema, Schema::Union(expected));
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case("protocol MyProtocol { enum Kind { FOO } }\n// trailing comment\n")]
+    #[case("protocol MyProtocol { enum Kind { FOO } }  /* trailing */  ")]
+    #[case("protocol MyProtocol { enum Kind { FOO } } // no newline")]
+    fn test_parse_trailing_whitespace_and_comments(#[case] input: &str) {
+        assert!(parse(input).is_ok());
+        assert!(parse_idl(input).is_ok());
+    }
+
+    #[rstest]
+    #[case("string hello(string name);", "hello", Schema::String, vec![("name", Schema::String)])]
+    #[case("int add(int a, long b);", "add", Schema::Int, vec![("a", Schema::Int), ("b", Schema::Long)])]
+    #[case("array<string> names( int  count , boolean sorted ) ;", "names", Schema::Array(Box::new(Schema::String)), vec![("count", Schema::Int), ("sorted", Schema::Boolean)])]
+    #[case("string ping();", "ping", Schema::String, vec![])]
+    #[case("void notify(string event);", "notify", Schema::Null, vec![("event", Schema::String)])]
+    #[case("int add(int a, int b);", "add", Schema::Int, vec![("a", Schema::Int), ("b", Schema::Int)])]
+    #[case("voided check();", "check", Schema::Ref { name: Name::new("voided").unwrap() }, vec![])]
+    fn test_parse_message(
+        #[case] input: &str,
+        #[case] name: &str,
+        #[case] response: Schema,
+        #[case] request: Vec<(&str, Schema)>,
+    ) {
+        let (tail, message) = parse_message(input).unwrap();
+        assert_eq!(tail, "");
+        assert_eq!(message.name, name);
+        assert_eq!(message.response, response);
+        let params: Vec<(&str, Schema)> = message
+            .request
+            .iter()
+            .map(|p| (p.name.as_str(), p.schema.clone()))
+            .collect();
+        assert_eq!(params, request);
+    }
+
+    #[rstest]
+    #[case(r#"string greet(string name = "world");"#, Some(Value::String("world".into())))]
+    #[case("string greet(string name);", None)]
+    #[case("string greet(int times = 2);", Some(Value::Number(2.into())))]
+    fn test_parse_message_param_default(#[case] input: &str, #[case] expected: Option<Value>) {
+        let (_tail, message) = parse_message(input).unwrap();
+        assert_eq!(message.request[0].default, expected);
+    }
+
+    #[test]
+    fn test_parse_message_param_invalid_default() {
+        let input = r#"string greet(int times = "many");"#;
+        match parse_message(input) {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.input, r#""many");"#);
+                assert_eq!(
+                    e.message.as_deref(),
+                    Some(r#"Invalid default for times, expected an int, found "many""#)
+                );
+            }
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_message_oneway() {
+        let (_tail, message) = parse_message("void fireAndForget(string msg) oneway;").unwrap();
+        assert_eq!(message.name, "fireAndForget");
+        assert!(message.one_way);
+
+        let (_tail, message) = parse_message("void notify(string msg);").unwrap();
+        assert!(!message.one_way);
+    }
+
+    #[test]
+    fn test_parse_message_oneway_not_void() {
+        let input = "string hello(string name) oneway;";
+        match parse_message(input) {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.input, input);
+                assert_eq!(
+                    e.message.as_deref(),
+                    Some("Oneway message hello must return void")
+                );
+            }
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_message_throws() {
+        let (_tail, message) =
+            parse_message("string hello(string name) throws NotFound, org.x.Forbidden;").unwrap();
+        assert_eq!(
+            message.errors,
+            vec![
+                Schema::Ref {
+                    name: Name::new("NotFound").unwrap()
+                },
+                Schema::Ref {
+                    name: Name::new("org.x.Forbidden").unwrap()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_idl_message_throws_declared_type() {
+        let input = r#"protocol Greeter {
+            record NotFound {
+                string message;
+            }
+            string hello(string name) throws NotFound;
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        assert_eq!(protocol.messages[0].errors, vec![protocol.types[0].clone()]);
+    }
+
+    #[rstest]
+    #[case(
+        r#"@namespace("org.example") protocol Shop { record R { string s; } }"#,
+        Some("org.example")
+    )]
+    #[case(
+        "/** The shop */\n@namespace(\"org.example\")\nprotocol Shop { enum E { A } }",
+        Some("org.example")
+    )]
+    #[case("protocol Shop { record R { string s; } }", None)]
+    fn test_parse_idl_namespace(#[case] input: &str, #[case] expected: Option<&str>) {
+        let protocol = parse_idl(input).unwrap();
+
+        assert_eq!(protocol.name, "Shop");
+        assert_eq!(protocol.namespace.as_deref(), expected);
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let input = "protocol Greeter { record Greeting { string text; } }";
+        let schemas = parse_reader(std::io::Cursor::new(input.as_bytes().to_vec())).unwrap();
+
+        assert_eq!(schemas, parse_schemas(input).unwrap());
+    }
+
+    #[test]
+    fn test_parse_bytes_invalid_utf8() {
+        let input = b"protocol Greeter { record \xff { string text; } }";
+        let err = parse_bytes(input).unwrap_err();
+
+        assert!(matches!(err, AvdlError::InvalidUtf8(_)));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let input = r#"error NotFound {
+            string message;
+        }"#;
+        let (_tail, schema) = parse_error(input).unwrap();
+        match schema {
+            Schema::Record(RecordSchema { name, fields, .. }) => {
+                assert_eq!(name, Name::new("NotFound").unwrap());
+                assert_eq!(fields[0].name, "message");
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_idl_with_errors() {
+        let input = r#"@namespace("org.x")
+        protocol Greeter {
+            error NotFound {
+                string message;
+            }
+            record Greeting {
+                string text;
+            }
+            Greeting hello(string name) throws NotFound;
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        assert!(protocol.is_error(&protocol.types[0]));
+        assert!(!protocol.is_error(&protocol.types[1]));
+        assert_eq!(protocol.messages[0].errors.len(), 1);
+        match &protocol.messages[0].errors[0] {
+            Schema::Record(RecordSchema { name, .. }) => assert_eq!(name.name, "NotFound"),
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_idl_with_messages() {
+        let input = r#"protocol Greeter {
+            record Greeting {
+                string text;
+            }
+            string hello(string name = "world");
+            void ping();
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        assert_eq!(protocol.name, "Greeter");
+        assert_eq!(protocol.types.len(), 1);
+        assert_eq!(protocol.messages.len(), 2);
+        assert_eq!(protocol.messages[0].name, "hello");
+        assert_eq!(
+            protocol.messages[0].request[0].default,
+            Some(Value::String(String::from("world")))
+        );
+        assert_eq!(protocol.messages[1].name, "ping");
+        assert_eq!(protocol.messages[1].response, Schema::Null);
+        assert!(protocol.messages[1].request.is_empty());
+    }
+
+    #[test]
+    fn test_parse_idl_message_named_response() {
+        let input = r#"@namespace("org.shop")
+        protocol Shop {
+            record Customer {
+                string id;
+            }
+            Customer getCustomer(string id);
+            void save(Customer customer);
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        match &protocol.messages[0].response {
+            Schema::Record(RecordSchema { name, fields, .. }) => {
+                assert_eq!(name.name, "Customer");
+                assert_eq!(fields[0].name, "id");
+            }
+            other => panic!("expected the Customer record, got {other:?}"),
+        }
+        assert!(matches!(
+            protocol.messages[1].request[0].schema,
+            Schema::Record(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_message_param_aliases() {
+        let (tail, message) = parse_message(r#"void f(string @aliases(["old"]) name);"#).unwrap();
+        assert_eq!(tail, "");
+        let param = &message.request[0];
+        assert_eq!(param.name, "name");
+        assert_eq!(param.schema, Schema::String);
+        assert_eq!(param.aliases, Some(vec![String::from("old")]));
+    }
+
+    #[test]
+    fn test_parse_keyword_type_name_lenient() {
+        let input = r#"@namespace("org.x")
+        protocol MyProtocol {
+            record int {
+                string name;
+            }
+            record Holder {
+                org.x.int shadowed;
+                int primitive;
+            }
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        match &protocol.types[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                // Only the fullname reaches the record, `int` is still the primitive
+                match &fields[0].schema {
+                    Schema::Record(RecordSchema { name, .. }) => assert_eq!(name.name, "int"),
+                    other => panic!("expected a record, got {other:?}"),
+                }
+                assert_eq!(fields[1].schema, Schema::Int);
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case("record int { string name; }", "int")]
+    #[case("record void { string name; }", "void")]
+    #[case("error record { string name; }", "record")]
+    #[case("enum oneway { A }", "oneway")]
+    #[case("fixed true(4);", "true")]
+    fn test_parse_keyword_type_name_strict(#[case] declaration: &str, #[case] name: &str) {
+        let input = format!("protocol MyProtocol {{ {declaration} }}");
+        let input = input.as_str();
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let expected = ParseError::new(
+            input,
+            input.find(declaration).unwrap(),
+            ParseErrorKind::Other(format!("Type name {name} is a reserved keyword")),
+        );
+        assert_eq!(parse_idl_with_options(input, &options), Err(expected));
+    }
+
+    #[test]
+    fn test_parse_qualified_enum_default_lenient() {
+        let input = r#"@namespace("org.x")
+        protocol MyProtocol {
+            enum Suit { SPADES, HEARTS }
+            record Card {
+                org.x.Suit suit = org.x.Suit.HEARTS;
+                Suit other = Suit.SPADES;
+            }
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        match &protocol.types[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                assert_eq!(fields[0].default, Some(Value::String("HEARTS".into())));
+                assert_eq!(fields[1].default, Some(Value::String("SPADES".into())));
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case("Suit s = Suit.HEARTS;", false, "Unknown symbol HEARTS for enum Suit")]
+    #[case(
+        "Suit s = Color.SPADES;",
+        false,
+        "Enum symbol Color.SPADES does not belong to Suit"
+    )]
+    #[case(
+        "Suit s = Suit.SPADES;",
+        true,
+        "Qualified enum symbol Suit.SPADES is not allowed in strict mode"
+    )]
+    fn test_parse_qualified_enum_default_error(
+        #[case] field: &str,
+        #[case] strict: bool,
+        #[case] message: &str,
+    ) {
+        let input = format!("protocol P {{ enum Suit {{ SPADES }} record Card {{ {field} }} }}");
+        let options = ParseOptions {
+            strict,
+            ..Default::default()
+        };
+        let expected = ParseError::new(
+            &input,
+            input.find(" = ").unwrap() + 3,
+            ParseErrorKind::Other(message.to_string()),
+        );
+        assert_eq!(parse_idl_with_options(&input, &options), Err(expected));
+    }
+
+    #[test]
+    fn test_parse_array_of_enum_default() {
+        let input = r#"protocol Paint {
+            enum Color { RED, GREEN, BLUE }
+            record Canvas {
+                array<Color> palette = [RED, Color.GREEN];
+            }
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        match &protocol.types[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                assert_eq!(fields[0].default, Some(json!(["RED", "GREEN"])));
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+
+        let input = input.replace("Color.GREEN", "PURPLE");
+        let expected = ParseError::new(
+            &input,
+            input.find("PURPLE").unwrap(),
+            ParseErrorKind::Other(String::from("Unknown symbol PURPLE for enum Color")),
+        );
+        assert_eq!(parse_idl(&input), Err(expected));
+    }
+
+    #[rstest]
+    #[case(r#"E e = "B";"#, Ok(json!("B")))]
+    #[case(r#"array<E> es = ["A", B];"#, Ok(json!(["A", "B"])))]
+    #[case(r#"E e = "PURPLE";"#, Err("Unknown symbol PURPLE for enum E"))]
+    #[case(
+        r#"array<E> es = ["PURPLE"];"#,
+        Err("Unknown symbol PURPLE for enum E")
+    )]
+    fn test_parse_quoted_enum_default(#[case] field: &str, #[case] expected: Result<Value, &str>) {
+        let input = format!("protocol P {{ enum E {{ A, B }} record R {{ {field} }} }}");
+        let out = parse_idl(&input)
+            .map(|protocol| match &protocol.types[1] {
+                Schema::Record(RecordSchema { fields, .. }) => fields[0].default.clone().unwrap(),
+                other => panic!("expected a record, got {other:?}"),
+            })
+            .map_err(|e| e.message);
+        assert_eq!(out, expected.map_err(String::from));
+    }
+
+    #[test]
+    fn test_enum_default_solver_non_string_item() {
+        let input = "protocol P { enum E { A, B } record R { array<E> es = [A]; } }";
+        let mut schemas = parse_schemas(input).unwrap();
+        let Schema::Record(RecordSchema { fields, .. }) = &mut schemas[1] else {
+            panic!("expected a record, got {:?}", schemas[1]);
+        };
+        fields[0].default = Some(json!(["A", 5]));
+
+        let error =
+            enum_default_solver(&mut schemas[1], &None, &ParseOptions::default()).unwrap_err();
+        assert_eq!(error.symbol, "5");
+        assert_eq!(error.reason, "Unknown symbol 5 for enum E");
+    }
+
+    #[rstest]
+    #[case("E e = PURPLE;", "= PURPLE")]
+    #[case("array<E> es = [A, PURPLE];", "PURPLE]")]
+    #[case("PURPLEish p = PURPLE;", "= PURPLE")]
+    fn test_parse_enum_default_error_offset(#[case] field: &str, #[case] default: &str) {
+        let input = format!(
+            "protocol P {{ enum E {{ A, B }} record PURPLEish {{ int x; }} record R {{ {field} }} }}"
+        );
+        let error = parse_idl(&input).unwrap_err();
+
+        assert_eq!(
+            error.offset,
+            input.find(default).unwrap() + default.find("PURPLE").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_unicode_name_offset() {
+        let input = "protocol P { record Café { int x; } record R { int café; } }";
+        let error = parse_idl(input).unwrap_err();
+
+        assert_eq!(error.offset, input.find("Café").unwrap());
+
+        let input = format!("{input}\n// trailing comment\n");
+        let error = parse_idl(&input).unwrap_err();
+        assert_eq!(error.offset, input.find("Café").unwrap());
+    }
+
+    #[test]
+    fn test_validate_reports_all_duplicates() {
+        let input = r#"protocol MyProtocol {
+            record Hello { string name; }
+            enum Hello { A, B }
+            fixed Hello(16);
+        }"#;
+        let expected = vec![
+            ParseError::new(
+                input,
+                input.find("enum Hello").unwrap(),
+                ParseErrorKind::DuplicateType {
+                    name: Name::new("Hello").unwrap(),
+                    kind: TypeKind::Enum,
+                    previous: TypeKind::Record,
+                },
+            ),
+            ParseError::new(
+                input,
+                input.find("fixed Hello").unwrap(),
+                ParseErrorKind::DuplicateType {
+                    name: Name::new("Hello").unwrap(),
+                    kind: TypeKind::Fixed,
+                    previous: TypeKind::Record,
+                },
+            ),
+        ];
+        assert_eq!(validate(input, &ParseOptions::default()), Err(expected));
+    }
+
+    #[test]
+    fn test_validate_reports_failed_import() {
+        let input = r#"protocol MyProtocol {
+            import schema "missing.avsc";
+            record Hello { string name; }
+            enum Hello { A, B }
+        }"#;
+        let errors = validate(input, &ParseOptions::default()).unwrap_err();
+        let offsets: Vec<usize> = errors.iter().map(|e| e.offset).collect();
+        assert_eq!(
+            offsets,
+            vec![
+                input.find("enum Hello").unwrap(),
+                input.find("import").unwrap()
+            ]
+        );
+        assert!(errors[1].message.ends_with("missing.avsc"));
+    }
+
+    #[rstest]
+    #[case(
+        "enum Suit { HEARTS } enum Suit { SPADES }",
+        "enum Suit",
+        "Duplicate enum org.cards.Suit, conflicts with an earlier enum"
+    )]
+    #[case(
+        "fixed Hash(16); fixed Hash(32);",
+        "fixed Hash(32)",
+        "Duplicate fixed org.cards.Hash, conflicts with an earlier fixed"
+    )]
+    #[case(
+        "enum Hash { MD5 } fixed Hash(16);",
+        "fixed Hash",
+        "Duplicate fixed org.cards.Hash, conflicts with an earlier enum"
+    )]
+    fn test_parse_duplicate_named_type(
+        #[case] declarations: &str,
+        #[case] duplicate: &str,
+        #[case] expected: &str,
+    ) {
+        let input = format!("@namespace(\"org.cards\") protocol Cards {{ {declarations} }}");
+        let error = parse_schemas(&input).unwrap_err();
+
+        assert_eq!(error.message, expected);
+        assert_eq!(error.offset, input.rfind(duplicate).unwrap());
+    }
+
+    #[test]
+    fn test_parse_schemas_error() {
+        let input = "protocol MyProtocol { record Hello { string name; int name; } }";
+        let expected = ParseError::new(
+            input,
+            input.find("int name").unwrap(),
+            ParseErrorKind::DuplicateField(String::from("name")),
+        );
+        assert_eq!(parse_schemas(input), Err(expected));
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let input =
+            "protocol MyProtocol {\n  record Hello {\n    string name\n    int age;\n  }\n}";
+        let error = parse_schemas(input).unwrap_err();
+
+        assert_eq!((error.line, error.column), (3, 16));
+        assert_eq!(
+            error.to_string(),
+            "Expected ; after field name at line 3, column 16"
+        );
+    }
+
+    #[test]
+    fn test_parse_reference_by_alias() {
+        let input = r#"@namespace("org.example")
+        protocol MyProtocol {
+            @aliases(["OldPoint", "org.legacy.Coordinate"])
+            record Point { int x; }
+            record Line {
+                OldPoint start;
+                org.legacy.Coordinate end;
+            }
+        }"#;
+        let schemas = parse_schemas(input).unwrap();
+
+        match &schemas[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                for field in fields {
+                    match &field.schema {
+                        Schema::Record(RecordSchema { name, .. }) => {
+                            assert_eq!(name.name, "Point")
+                        }
+                        other => panic!("expected a record, got {other:?}"),
+                    }
+                }
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_bom() {
+        let input = "\u{feff}protocol MyProtocol {\n  record Hello { string name; }\n}";
+        let schemas = parse_schemas(input).unwrap();
+        assert_eq!(schemas.len(), 1);
+
+        let input = "\u{feff}protocol MyProtocol {\n  record Hello { string name; int name; }\n}";
+        let error = parse_schemas(input).unwrap_err();
+        assert_eq!((error.line, error.column), (2, 31));
+    }
+
+    #[test]
+    fn test_parse_schema_file() {
+        let input = r#"
+        record Main {
+            Kind kind;
+            string name;
+        }
+        enum Kind { A, B }
+        "#;
+        let protocol = parse_idl(input).unwrap();
+
+        assert_eq!(protocol.name, "");
+        assert_eq!(protocol.types.len(), 2);
+        match &protocol.types[0] {
+            Schema::Record(RecordSchema { name, fields, .. }) => {
+                assert_eq!(name.fullname(None), "Main");
+                assert!(matches!(fields[0].schema, Schema::Enum(_)));
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_forward_reference() {
+        let input = r#"protocol P {
+            record A { B b; }
+            record B { C c; }
+            record C { int x; }
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        let Schema::Record(a) = &protocol.types[0] else {
+            panic!("expected a record, got {:?}", protocol.types[0]);
+        };
+        let Schema::Record(b) = &a.fields[0].schema else {
+            panic!("expected a record, got {:?}", a.fields[0].schema);
+        };
+        assert_eq!(b.name.name, "B");
+        match &b.fields[0].schema {
+            Schema::Record(c) => assert_eq!(c.name.name, "C"),
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_protocol_comments_between_declarations() {
+        let input = r#"protocol P {
+            // Colors
+            /* of the palette */
+            /** Primary colors */
+            enum Color { RED, GREEN }
+            /** Digest */ // 16 bytes
+            fixed MD5(16);
+            // People
+            /** Somebody */
+            record Person { string name; }
+            /* the greeting */ /** Says hello */
+            string hello(string name);
+            // done
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        let docs: Vec<Option<&str>> = protocol
+            .types
+            .iter()
+            .map(|schema| match schema {
+                Schema::Record(RecordSchema { doc, .. })
+                | Schema::Enum(EnumSchema { doc, .. })
+                | Schema::Fixed(FixedSchema { doc, .. }) => doc.as_deref(),
+                other => panic!("expected a named type, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            docs,
+            vec![Some("Primary colors"), Some("Digest"), Some("Somebody")]
+        );
+        assert_eq!(protocol.messages[0].doc.as_deref(), Some("Says hello"));
+    }
+
+    #[test]
+    fn test_parse_protocol_enum_default() {
+        let input = r#"protocol Shapes {
+            enum Shape { SQUARE, TRIANGLE } = SQUARE;
+            record Drawing { Shape shape; }
+        }"#;
+        let schemas = parse_schemas(input).unwrap();
+
+        match &schemas[0] {
+            Schema::Enum(EnumSchema { default, .. }) => {
+                assert_eq!(default.as_deref(), Some("SQUARE"))
+            }
+            other => panic!("expected an enum, got {other:?}"),
+        }
+        match &schemas[1] {
+            Schema::Record(RecordSchema { fields, .. }) => match &fields[0].schema {
+                Schema::Enum(EnumSchema { default, .. }) => {
+                    assert_eq!(default.as_deref(), Some("SQUARE"))
+                }
+                other => panic!("expected an enum, got {other:?}"),
+            },
+            other => panic!("expected a record, got {other:?}"),
+        }
+
+        let input = input.replace("= SQUARE", "= CIRCLE");
+        let expected = ParseError::new(
+            &input,
+            input.find("= CIRCLE").unwrap(),
+            ParseErrorKind::InvalidDefault {
+                field: String::from("Shape"),
+                reason: String::from("CIRCLE is not one of its symbols"),
+            },
+        );
+        assert_eq!(parse_schemas(&input), Err(expected));
+    }
+
+    #[test]
+    fn test_parse_inlined_types_namespace() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            record Address { string street; }
+            record Customer { Address home; }
+            @namespace("org.other")
+            record Order {
+                org.example.Customer customer;
+                array<org.example.Address> stops;
+            }
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        let Schema::Record(order) = &protocol.types[2] else {
+            panic!("expected a record, got {:?}", protocol.types[2]);
+        };
+        assert_eq!(order.name.fullname(None), "org.other.Order");
+        let Schema::Record(customer) = &order.fields[0].schema else {
+            panic!("expected a record, got {:?}", order.fields[0].schema);
+        };
+        assert_eq!(customer.name.fullname(None), "org.example.Customer");
+        match &customer.fields[0].schema {
+            Schema::Record(address) => {
+                assert_eq!(address.name.fullname(None), "org.example.Address")
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+        match &order.fields[1].schema {
+            Schema::Array(items) => match items.as_ref() {
+                Schema::Record(address) => {
+                    assert_eq!(address.name.fullname(None), "org.example.Address")
+                }
+                other => panic!("expected a record, got {other:?}"),
+            },
+            other => panic!("expected an array, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_reference_named_types() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            record Address { string street; }
+            record Customer {
+                Address home;
+                Address work;
+            }
+            Address locate(Customer customer);
+        }"#;
+        let options = ParseOptions {
+            reference_named_types: true,
+            ..Default::default()
+        };
+        let protocol = parse_idl_with_options(input, &options).unwrap();
+        let address = Name::new("org.example.Address").unwrap();
+
+        assert!(matches!(&protocol.types[0], Schema::Record(_)));
+        match &protocol.types[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                assert_eq!(
+                    fields[0].schema,
+                    Schema::Ref {
+                        name: address.clone()
+                    }
+                );
+                assert_eq!(
+                    fields[1].schema,
+                    Schema::Ref {
+                        name: address.clone()
+                    }
+                );
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+        assert_eq!(protocol.messages[0].response, Schema::Ref { name: address });
+
+        // The first use in full, the next ones by name
+        let input = "protocol P { record A { B first; B second; } record B { int x; } }";
+        let protocol = parse_idl_with_options(input, &options).unwrap();
+        match &protocol.types[..] {
+            [Schema::Record(RecordSchema { fields, .. }), Schema::Ref { name }] => {
+                assert!(matches!(&fields[0].schema, Schema::Record(_)));
+                assert!(matches!(&fields[1].schema, Schema::Ref { .. }));
+                assert_eq!(name.name, "B");
+            }
+            other => panic!("expected a record and a reference, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_protocols() {
+        let input = r#"
+        @namespace("org.a")
+        protocol A {
+            record Item { int id; }
+        }
+        // The second one
+        @namespace("org.b")
+        protocol B {
+            record Item { string id; }
+            Item get(int id);
+        }
+        "#;
+        let protocols = parse_protocols(input).unwrap();
+
+        let names: Vec<&str> = protocols.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["A", "B"]);
+        assert_eq!(protocols[0].namespace.as_deref(), Some("org.a"));
+        assert_eq!(protocols[1].namespace.as_deref(), Some("org.b"));
+        assert_eq!(protocols[1].messages[0].name, "get");
+    }
+
+    #[test]
+    fn test_parse_protocols_error() {
+        let input = "protocol A { record X { int a; } }\nprotocol B { record Y { Z z; } }";
+        let error = parse_protocols(input).unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnresolvedReference(Name::new("Z").unwrap())
+        );
+        assert_eq!((error.line, error.column), (2, 25));
+
+        let input = "protocol A { record X { int a; } }\nrecord Y { int b; }";
+        let error = parse_protocols(input).unwrap_err();
+        assert_eq!(error.message, "Expected a protocol");
+        assert_eq!((error.line, error.column), (2, 1));
+    }
+
+    #[test]
+    fn test_parse_protocol_doc() {
+        let input = r#"
+        /** Greets people */
+        @namespace("org.example")
+        protocol Greeter {
+            record Greeting { string message; }
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        assert_eq!(protocol.name, "Greeter");
+        assert_eq!(protocol.doc.as_deref(), Some("Greets people"));
+
+        let protocol = parse_idl("protocol Greeter { record Greeting { string message; } }");
+        assert_eq!(protocol.unwrap().doc, None);
+    }
+
+    #[test]
+    fn test_parse_unresolved_reference() {
+        let input = "protocol P {\n  record A { Missing m; }\n}";
+        let expected = ParseError::new(
+            input,
+            input.find("Missing").unwrap(),
+            ParseErrorKind::UnresolvedReference(Name::new("Missing").unwrap()),
+        );
+        assert_eq!(parse_schemas(input), Err(expected));
+
+        let input = "protocol P {\n  record A { int a; }\n  A hello(org.x.Missing m);\n}";
+        let error = parse_idl(input).unwrap_err();
+        assert_eq!(error.message, "Unknown type org.x.Missing");
+        assert_eq!((error.line, error.column), (3, 11));
+
+        let input = "protocol P { record Zed { int x; } record A { /* Z */ Z z; } }";
+        let error = parse_idl(input).unwrap_err();
+        assert_eq!(error.message, "Unknown type Z");
+        assert_eq!(error.offset, input.find("Z z").unwrap());
+
+        let input = "protocol P { record Zed { int x; } Z hello() throws Zed, Z; }";
+        let error = parse_idl(input).unwrap_err();
+        assert_eq!(error.offset, input.find("Z hello").unwrap());
+    }
+
+    #[rstest]
+    #[case("protocol P { record A { Missing m; } }\n")]
+    #[case("protocol P { record A { Missing m; } }\n// trailing comment\n")]
+    fn test_parse_unresolved_reference_trailing_input(#[case] input: &str) {
+        let error = parse_idl(input).unwrap_err();
+        assert_eq!((error.line, error.column), (1, 25));
+
+        let input = format!("{input}protocol Q {{ record B {{ int b; }} }}");
+        let error = parse_protocols(&input).unwrap_err();
+        assert_eq!((error.line, error.column), (1, 25));
+    }
+
+    #[test]
+    fn test_parse_mutual_reference() {
+        let input = r#"protocol P {
+            record A { union { null, B } b; }
+            record B { union { null, A } a; }
+        }"#;
+        let protocol = parse_idl(input).unwrap();
+
+        let Schema::Record(a) = &protocol.types[0] else {
+            panic!("expected a record, got {:?}", protocol.types[0]);
+        };
+        let Schema::Union(union_schema) = &a.fields[0].schema else {
+            panic!("expected a union, got {:?}", a.fields[0].schema);
+        };
+        let Schema::Record(b) = &union_schema.variants()[1] else {
+            panic!("expected a record, got {:?}", union_schema.variants()[1]);
+        };
+        let Schema::Union(union_schema) = &b.fields[0].schema else {
+            panic!("expected a union, got {:?}", b.fields[0].schema);
+        };
+        assert!(matches!(&union_schema.variants()[1], Schema::Ref { name } if name.name == "A"));
+    }
+
+    #[test]
+    fn test_parse_namespace_statement() {
+        let input = r#"
+        namespace org.example;
+        record Main {
+            Kind kind;
+        }
+        enum Kind { A, B }
+        "#;
+        let protocol = parse_idl(input).unwrap();
+
+        assert_eq!(protocol.namespace.as_deref(), Some("org.example"));
+        match &protocol.types[..] {
+            [Schema::Record(RecordSchema { name, fields, .. }), Schema::Enum(kind)] => {
+                assert_eq!(name.fullname(None), "org.example.Main");
+                assert_eq!(kind.name.fullname(None), "org.example.Kind");
+                assert!(matches!(fields[0].schema, Schema::Enum(_)));
+            }
+            other => panic!("expected a record and an enum, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_schema_file_message() {
+        let input = "record Main { string name; }\nstring hello(string name);";
+        assert!(parse_idl(input).is_err());
+    }
+
+    #[test]
+    fn test_parse_trailing_junk() {
+        let input = "protocol MyProtocol { enum Kind { FOO } } junk";
+        match parse(input) {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.input, "junk");
+                assert_eq!(
+                    e.message.as_deref(),
+                    Some("Unexpected input after the protocol")
+                );
+            }
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_protocol_namespace_on_enum_and_fixed() {
+        let input = r#"@namespace("org.example")
+        protocol MyProtocol {
+            enum Kind { FOO, BAR }
+            fixed MD5(16);
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+
+        match &schemas[0] {
+            Schema::Enum(EnumSchema { name, .. }) => {
+                assert_eq!(name.fullname(None), "org.example.Kind");
+            }
+            other => panic!("expected an enum, got {other:?}"),
+        }
+        match &schemas[1] {
+            Schema::Fixed(FixedSchema { name, .. }) => {
+                assert_eq!(name.fullname(None), "org.example.MD5");
+            }
+            other => panic!("expected a fixed, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_protocol_same_name_in_namespaces() {
+        let input = r#"@namespace("org.a")
+        protocol MyProtocol {
+            @namespace("org.b")
+            record Thing { int b; }
+            record Thing { string a; }
+            record LocalHolder { Thing thing; }
+            @namespace("org.b")
+            record RemoteHolder { Thing thing; }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+
+        let fullnames: Vec<String> = schemas
+            .iter()
+            .map(|schema| match schema {
+                Schema::Record(RecordSchema { name, .. }) => name.fullname(None),
+                other => panic!("expected a record, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            fullnames,
+            vec![
+                "org.b.Thing",
+                "org.a.Thing",
+                "org.a.LocalHolder",
+                "org.b.RemoteHolder"
+            ]
+        );
+        // Each holder gets the `Thing` of its own namespace
+        for (holder, expected) in [(&schemas[2], "a"), (&schemas[3], "b")] {
+            match holder {
+                Schema::Record(RecordSchema { fields, .. }) => match &fields[0].schema {
+                    Schema::Record(RecordSchema { fields, .. }) => {
+                        assert_eq!(fields[0].name, expected)
+                    }
+                    other => panic!("expected a record, got {other:?}"),
+                },
+                other => panic!("expected a record, got {other:?}"),
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("01:00:00", 3_600_000)]
+    #[case("12:34:56.789", 45_296_789)]
+    #[case("00:00:01.5", 1_500)]
+    fn test_time_of_day_millis(#[case] input: &str, #[case] expected: i32) {
+        assert_eq!(time_of_day_millis(input), Ok(expected));
+    }
+
+    #[rstest]
+    #[case("1:00:00")]
+    #[case("24:00:00")]
+    #[case("01:00")]
+    #[case("01:00:00.1234")]
+    fn test_time_of_day_millis_invalid(#[case] input: &str) {
+        assert!(time_of_day_millis(input).is_err());
+    }
+
+    #[rstest]
+    #[case(r#"time_ms t = "01:00:00";"#, false, Ok(Value::from(3_600_000)))]
+    #[case(
+        r#"time_ms t = "01:00:00";"#,
+        true,
+        Err(String::from("Default of time_ms field t must be an integer in strict mode"))
+    )]
+    #[case(
+        r#"union { time_ms, null } t = "01:00:00";"#,
+        false,
+        Ok(Value::from(3_600_000))
+    )]
+    #[case(
+        r#"union { time_ms, null } t = "01:00:00";"#,
+        true,
+        Err(String::from("Default of time_ms field t must be an integer in strict mode"))
+    )]
+    #[case(r#"array<time_ms> t = ["01:00:00"];"#, false, Ok(json!([3_600_000])))]
+    #[case(
+        r#"array<time_ms> t = ["01:00:00"];"#,
+        true,
+        Err(String::from("Default of time_ms field t must be an integer in strict mode"))
+    )]
+    fn test_parse_time_of_day_default(
+        #[case] field: &str,
+        #[case] strict: bool,
+        #[case] expected: Result<Value, String>,
+    ) {
+        let input = format!("protocol MyProtocol {{ record Alarm {{ {field} }} }}");
+        let options = ParseOptions {
+            strict,
+            ..Default::default()
+        };
+        let out = parse_idl_with_options(&input, &options);
+        let out = out
+            .map(|protocol| match &protocol.types[0] {
+                Schema::Record(RecordSchema { fields, .. }) => fields[0].default.clone().unwrap(),
+                other => panic!("expected a record, got {other:?}"),
+            })
+            .map_err(|e| e.message);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_parse_record_time_of_day_default() {
+        let (_tail, schema) =
+            parse_record(r#"record Alarm { time_ms t = "00:00:01.5"; }"#).unwrap();
+
+        match schema {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                assert_eq!(fields[0].default, Some(Value::from(1_500)))
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_protocol_union_with_reference() {
+        let input = r#"@namespace("org.example")
+        protocol MyProtocol {
+            record PreviouslyDefined {
+                string name;
+            }
+            record Holder {
+                union { null, PreviouslyDefined } field = null;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+
+        match &schemas[1] {
+            Schema::Record(RecordSchema { fields, .. }) => match &fields[0].schema {
+                Schema::Union(union_schema) => {
+                    let variants = union_schema.variants();
+                    assert_eq!(variants[0], Schema::Null);
+                    match &variants[1] {
+                        Schema::Record(RecordSchema { name, fields, .. }) => {
+                            assert_eq!(name.name, "PreviouslyDefined");
+                            assert_eq!(fields[0].name, "name");
+                        }
+                        other => panic!("expected the referenced record, got {other:?}"),
+                    }
+                }
+                other => panic!("expected a union, got {other:?}"),
+            },
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case("Temperature", "celsius", false, None)]
+    #[case("Temperature", "celsius", true, None)]
+    #[case("Température", "celsius", true, None)]
+    #[case(
+        "Température",
+        "celsius",
+        false,
+        Some("Name Température is not allowed, only ASCII letters, digits and _ are")
+    )]
+    #[case(
+        "Temperature",
+        "degrés",
+        false,
+        Some("Name degrés is not allowed, only ASCII letters, digits and _ are")
+    )]
+    fn test_parse_unicode_names(
+        #[case] record: &str,
+        #[case] field: &str,
+        #[case] unicode_names: bool,
+        #[case] expected: Option<&str>,
+    ) {
+        let input = format!("protocol MyProtocol {{ record {record} {{ int {field}; }} }}");
+        let options = ParseOptions {
+            unicode_names,
+            ..Default::default()
+        };
+        let out = parse_idl_with_options(&input, &options);
+        assert_eq!(out.err().map(|e| e.message).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_parse_protocol_recursive_record() {
+        let input = r#"@namespace("org.example")
+        protocol MyProtocol {
+            record Node {
+                int value;
+                union { null, Node } next;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+
+        match &schemas[0] {
+            Schema::Record(RecordSchema { fields, .. }) => match &fields[1].schema {
+                Schema::Union(union_schema) => {
+                    assert_eq!(
+                        union_schema.variants()[1],
+                        Schema::Ref {
+                            name: Name::new("Node").unwrap()
+                        }
+                    );
+                }
+                other => panic!("expected a union, got {other:?}"),
+            },
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_record_field_positions() {
+        let input = r#"record Point {
+            int x;
+            int y;
+            union { null, string } label = null;
+        }"#;
+        let (_tail, schema) = parse_record(input).unwrap();
+        match schema {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                let positions: Vec<usize> = fields.iter().map(|f| f.position).collect();
+                assert_eq!(positions, vec![0, 1, 2]);
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case(r#"int x = 1 @order("ignore");"#)]
+    #[case(r#"int x @order("ignore");"#)]
+    fn test_parse_field_misplaced_annotation(#[case] input: &str) {
+        match parse_field(input) {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.input, r#"@order("ignore");"#);
+                assert_eq!(
+                    e.message.as_deref(),
+                    Some("Annotations of field x must come between its type and its name")
+                );
+            }
+            other => panic!("expected a failure, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_record_lookup() {
+        let input = r#"record Point {
+            int x;
+            int @aliases(["ordinate"]) y;
+        }"#;
+        let (_tail, schema) = parse_record(input).unwrap();
+        match schema {
+            Schema::Record(RecordSchema { lookup, .. }) => {
+                let expected = BTreeMap::from([
+                    (String::from("x"), 0),
+                    (String::from("y"), 1),
+                    (String::from("ordinate"), 1),
+                ]);
+                assert_eq!(lookup, expected);
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            enum Status { ACTIVE, INACTIVE }
+            fixed Hash(16);
+            record Customer {
+                Status status = ACTIVE;
+                union { null, Hash } hash = null;
+                decimal(10, 2) balance;
+            }
+            record Order {
+                Customer customer;
+                array<Customer> others = [];
+                map<Status> history;
+            }
+        }"#;
+        for schema in parse_schemas(input).unwrap() {
+            assert_eq!(validate_schema(&schema).map_err(|e| e.to_string()), Ok(()));
+        }
+
+        let schema = Schema::Enum(EnumSchema {
+            name: Name::new("Broken").unwrap(),
+            aliases: None,
+            doc: None,
+            symbols: vec![String::from("A"), String::from("1X")],
+            default: None,
+            attributes: BTreeMap::new(),
+        });
+        assert!(matches!(
+            validate_schema(&schema),
+            Err(AvdlError::InvalidSchema(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_cross_namespace_references() {
+        let input = r#"@namespace("x.y") protocol P {
+            @namespace("a.b") record A { c.d.B b; }
+            @namespace("c.d") record B { union { null, a.b.A } a = null; }
+        }"#;
+        let schemas = parse_schemas(input).unwrap();
+
+        let field_type = |schema: &Schema| match schema {
+            Schema::Record(RecordSchema { fields, .. }) => fields[0].schema.clone(),
+            other => panic!("expected a record, got {other:?}"),
+        };
+        match field_type(&schemas[0]) {
+            Schema::Record(RecordSchema { name, .. }) => {
+                assert_eq!(name, Name::new("c.d.B").unwrap())
+            }
+            other => panic!("expected c.d.B, got {other:?}"),
+        }
+        match field_type(&schemas[1]) {
+            Schema::Union(union_schema) => match &union_schema.variants()[1] {
+                Schema::Record(RecordSchema { name, .. }) => {
+                    assert_eq!(name, &Name::new("a.b.A").unwrap())
+                }
+                other => panic!("expected a.b.A, got {other:?}"),
+            },
+            other => panic!("expected a union, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case("Point", None)]
+    #[case("Pointer", Some("Unknown type Pointer"))]
+    fn test_validate_references(#[case] reference: &str, #[case] expected: Option<&str>) {
+        let input = format!(
+            "protocol MyProtocol {{
+                record Point {{ int x; }}
+                record Line {{ {reference} start; array<{reference}> points; }}
+            }}"
+        );
+        let mut names_ref = HashMap::new();
+        let (_tail, protocol) =
+            parse_protocol(&input, &mut names_ref, &ParseOptions::default()).unwrap();
+        let types = protocol.types.clone();
+
+        let out = validate_references(&protocol.types, &names_ref);
+        assert_eq!(out.err().map(|e| e.to_string()).as_deref(), expected);
+        // The references are left as they were parsed
+        assert_eq!(protocol.types, types);
+        match &protocol.types[1] {
+            Schema::Record(RecordSchema { fields, .. }) => {
+                assert!(matches!(fields[0].schema, Schema::Ref { .. }))
+            }
+            other => panic!("expected a record, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case(r#"@java-class("java.util.Date")"#, ("java-class", json!("java.util.Date")))]
+    #[case(r#"@my.props( {"a": [1, true, null], "b": {}} )"#, ("my.props", json!({"a": [1, true, null], "b": {}})))]
+    #[case(r#"@size(-1.5e3)"#, ("size", json!(-1500.0)))]
+    fn test_parse_custom_annotation(#[case] input: &str, #[case] expected: (&str, Value)) {
+        let expected = (expected.0.to_string(), expected.1);
+        assert_eq!(parse_custom_annotation(input), Ok(("", expected)));
+    }
+
+    #[test]
+    fn test_parse_custom_annotation_known() {
+        assert!(parse_custom_annotation(r#"@order("ignore")"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_record_custom_attributes() {
+        let input = r#"@my-prop("x") @namespace("org.example") @version(2) record Hello {
+            string name;
+        }"#;
+        let (_tail, schema) = parse_record(input).unwrap();
+        let Schema::Record(record) = schema else {
+            panic!("not a record: {schema:?}");
+        };
+
+        assert_eq!(record.name.namespace.as_deref(), Some("org.example"));
+        let expected = BTreeMap::from([
+            (String::from("my-prop"), json!("x")),
+            (String::from("version"), json!(2)),
+        ]);
+        assert_eq!(record.attributes, expected);
+    }
+
+    #[test]
+    fn test_parse_enum_and_fixed_custom_attributes() {
+        let expected = BTreeMap::from([(String::from("my-prop"), json!("x"))]);
+
+        let (_tail, schema) = parse_enum(r#"@my-prop("x") enum Kind { A, B }"#).unwrap();
+        let Schema::Enum(enum_schema) = schema else {
+            panic!("not an enum: {schema:?}");
+        };
+        assert_eq!(enum_schema.attributes, expected);
+
+        let (_tail, schema) = parse_fixed(r#"@my-prop("x") fixed MD5(16);"#).unwrap();
+        let Schema::Fixed(fixed) = schema else {
+            panic!("not a fixed: {schema:?}");
+        };
+        assert_eq!(fixed.attributes, expected);
+    }
+
+    #[test]
+    fn test_parse_record_field_custom_attributes() {
+        let input = r#"@java-class("java.util.Date") @deprecated(true) long ts;"#;
+        let (_tail, field) = parse_record_field(input).unwrap();
+
+        assert_eq!(field.name, "ts");
+        assert_eq!(field.schema, Schema::Long);
+        let expected = BTreeMap::from([
+            (String::from("deprecated"), json!(true)),
+            (String::from("java-class"), json!("java.util.Date")),
+        ]);
+        assert_eq!(field.custom_attributes, expected);
+    }
+
     #[test]
     fn test_parse_big_record() {
         let input_schema = r#"@namespace("org.apache.avro.someOtherNamespace")
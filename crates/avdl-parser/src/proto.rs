@@ -0,0 +1,230 @@
+//! Translate parsed Avro schemas into Protobuf (`proto3`) definitions.
+//!
+//! - records become messages and enums become proto enums
+//! - primitive and logical types map to the closest proto scalar
+//! - `array<T>` becomes `repeated T` and `map<T>` becomes `map<string, T>`
+//! - a union of `null` and a single type becomes an `optional` field, any other
+//!   union becomes a `oneof`
+//!
+//! Protobuf can't nest collections, so arrays and maps that are not directly
+//! the type of a field are emitted as `bytes`.
+
+use apache_avro::schema::{EnumSchema, Name, RecordSchema, Schema};
+
+#[derive(Default)]
+struct ProtoWriter {
+    definitions: Vec<String>,
+    seen: Vec<Name>,
+    uses_null: bool,
+}
+
+/// Emit a `.proto` file containing `schema` and every named type declared inside it.
+pub fn to_proto(schema: &Schema) -> String {
+    let mut writer = ProtoWriter::default();
+    writer.collect(schema);
+
+    let mut out = String::from("syntax = \"proto3\";\n");
+    if let Some(namespace) = namespace_of(schema) {
+        out.push_str(&format!("\npackage {namespace};\n"));
+    }
+    if writer.uses_null {
+        out.push_str("\nimport \"google/protobuf/struct.proto\";\n");
+    }
+    for definition in writer.definitions {
+        out.push('\n');
+        out.push_str(&definition);
+    }
+    out
+}
+
+fn namespace_of(schema: &Schema) -> Option<&String> {
+    match schema {
+        Schema::Record(RecordSchema { name, .. }) | Schema::Enum(EnumSchema { name, .. }) => {
+            name.namespace.as_ref()
+        }
+        _ => None,
+    }
+}
+
+impl ProtoWriter {
+    // Walk the schema depth first, so nested types are declared before the
+    // messages using them.
+    fn collect(&mut self, schema: &Schema) {
+        match schema {
+            Schema::Record(record) => {
+                if self.seen.contains(&record.name) {
+                    return;
+                }
+                self.seen.push(record.name.clone());
+                for field in &record.fields {
+                    self.collect(&field.schema);
+                }
+                let message = self.message(record);
+                self.definitions.push(message);
+            }
+            Schema::Enum(enum_schema) => {
+                if self.seen.contains(&enum_schema.name) {
+                    return;
+                }
+                self.seen.push(enum_schema.name.clone());
+                self.definitions.push(enumeration(enum_schema));
+            }
+            Schema::Array(schema) | Schema::Map(schema) => self.collect(schema),
+            Schema::Union(union_schema) => {
+                for variant in union_schema.variants() {
+                    self.collect(variant);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn message(&mut self, record: &RecordSchema) -> String {
+        let mut out = doc_comment(&record.doc, "");
+        out.push_str(&format!("message {} {{\n", record.name.name));
+        let mut number = 1;
+        for field in &record.fields {
+            out.push_str(&doc_comment(&field.doc, "  "));
+            out.push_str(&self.field(&field.name, &field.schema, &mut number));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn field(&mut self, name: &str, schema: &Schema, number: &mut usize) -> String {
+        let line = match schema {
+            Schema::Array(items) => {
+                format!("  repeated {} {name} = {number};\n", self.scalar(items))
+            }
+            Schema::Map(values) => {
+                format!(
+                    "  map<string, {}> {name} = {number};\n",
+                    self.scalar(values)
+                )
+            }
+            Schema::Union(union_schema) => {
+                let variants: Vec<&Schema> = union_schema
+                    .variants()
+                    .iter()
+                    .filter(|s| **s != Schema::Null)
+                    .collect();
+                let nullable = variants.len() < union_schema.variants().len();
+                match variants.as_slice() {
+                    [Schema::Array(_) | Schema::Map(_)] => {
+                        return self.field(name, variants[0], number)
+                    }
+                    [variant] if nullable => {
+                        format!("  optional {} {name} = {number};\n", self.scalar(variant))
+                    }
+                    _ => return self.oneof(name, &variants, number),
+                }
+            }
+            schema => format!("  {} {name} = {number};\n", self.scalar(schema)),
+        };
+        *number += 1;
+        line
+    }
+
+    fn oneof(&mut self, name: &str, variants: &[&Schema], number: &mut usize) -> String {
+        let mut out = format!("  oneof {name} {{\n");
+        for variant in variants {
+            let proto_type = self.scalar(variant);
+            let suffix = proto_type.replace('.', "_").to_lowercase();
+            out.push_str(&format!("    {proto_type} {name}_{suffix} = {number};\n"));
+            *number += 1;
+        }
+        out.push_str("  }\n");
+        out
+    }
+
+    // The proto type used to hold a single value of `schema`
+    fn scalar(&mut self, schema: &Schema) -> String {
+        match schema {
+            Schema::Null => {
+                self.uses_null = true;
+                String::from("google.protobuf.NullValue")
+            }
+            Schema::Boolean => String::from("bool"),
+            Schema::Int | Schema::Date | Schema::TimeMillis => String::from("int32"),
+            Schema::Long
+            | Schema::TimeMicros
+            | Schema::TimestampMillis
+            | Schema::TimestampMicros => String::from("int64"),
+            Schema::Float => String::from("float"),
+            Schema::Double => String::from("double"),
+            Schema::String | Schema::Uuid => String::from("string"),
+            Schema::Record(RecordSchema { name, .. })
+            | Schema::Enum(EnumSchema { name, .. })
+            | Schema::Ref { name } => name.name.clone(),
+            // bytes, fixed, decimal, duration and nested collections
+            _ => String::from("bytes"),
+        }
+    }
+}
+
+fn enumeration(enum_schema: &EnumSchema) -> String {
+    let mut out = doc_comment(&enum_schema.doc, "");
+    out.push_str(&format!("enum {} {{\n", enum_schema.name.name));
+    for (i, symbol) in enum_schema.symbols.iter().enumerate() {
+        out.push_str(&format!("  {symbol} = {i};\n"));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn doc_comment(doc: &Option<String>, indent: &str) -> String {
+    match doc {
+        Some(doc) => doc
+            .lines()
+            .map(|line| format!("{indent}// {}\n", line.trim()))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_to_proto() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            enum Status { ACTIVE, INACTIVE }
+            record Customer {
+                string name;
+                long id;
+                Status status;
+                array<string> tags;
+                map<int> counts;
+                union { null, string } nickname = null;
+                union { int, string } code;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+        let expected = r#"syntax = "proto3";
+
+package org.example;
+
+enum Status {
+  ACTIVE = 0;
+  INACTIVE = 1;
+}
+
+message Customer {
+  string name = 1;
+  int64 id = 2;
+  Status status = 3;
+  repeated string tags = 4;
+  map<string, int32> counts = 5;
+  optional string nickname = 6;
+  oneof code {
+    int32 code_int32 = 7;
+    string code_string = 8;
+  }
+}
+"#;
+        assert_eq!(to_proto(&schemas[1]), expected);
+    }
+}
@@ -1,26 +1,89 @@
-use std::collections::HashMap;
+use apache_avro::schema::{Name, Namespace, RecordField, RecordSchema, Schema};
+use serde_json::Value;
 
-use crate::schema::{Documentation, Namespace, Schema};
+/// An RPC declaration of a protocol, e.g. `string hello(string name);`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub name: String,
+    pub doc: Option<String>,
+    pub request: Vec<RecordField>,
+    pub response: Schema,
+    /// Types listed in the `throws` clause
+    pub errors: Vec<Schema>,
+    /// Declared `oneway`, the caller doesn't wait for a response
+    pub one_way: bool,
+    /// Parameters of `request` with a float or double default written as an
+    /// integer, see [`Protocol::integral_defaults`]
+    pub integral_defaults: Vec<String>,
+}
+
+/// A whole Avdl protocol: the named types it declares and its messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Protocol {
+    /// Empty for schema files, declaring types without a protocol
+    pub name: String,
+    /// The doc comment above `protocol`
+    pub doc: Option<String>,
+    pub namespace: Namespace,
+    pub types: Vec<Schema>,
+    /// Names of the records in `types` declared with `error`
+    pub error_names: Vec<Name>,
+    /// Fields of the records in `types`, by record name and field name, with a
+    /// float or double default written as an integer, like `double x = 5;`.
+    /// The default is a float all the same, this is a hint to write it back.
+    pub integral_defaults: Vec<(Name, String)>,
+    pub messages: Vec<Message>,
+}
+
+impl Protocol {
+    /// Whether `schema` is one of the `error` records of the protocol
+    pub fn is_error(&self, schema: &Schema) -> bool {
+        match schema {
+            Schema::Record(RecordSchema { name, .. }) => self.error_names.contains(name),
+            _ => false,
+        }
+    }
+
+    /// Whether the default of `field` of the record `record` is written as an integer
+    pub fn is_integral_default(&self, record: &Name, field: &str) -> bool {
+        self.integral_defaults
+            .iter()
+            .any(|(name, name_field)| name == record && name_field == field)
+    }
+
+    /// Options to write the types of the protocol back as they were declared
+    pub fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            integral_defaults: self.integral_defaults.clone(),
+        }
+    }
+}
 
-enum Types {
-    Record(Schema),
-    Enum(Schema),
-    Fixed(Schema),
-    Error(Schema),
+/// Options of the Avdl and JSON writers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WriteOptions {
+    /// Record fields, by record name and field name, whose float or double
+    /// default is written as an integer, like `5` rather than `5.0`
+    pub integral_defaults: Vec<(Name, String)>,
 }
 
-struct Message {
-    name: String,
-    doc: Documentation,
-    request: Vec<HashMap<String, String>>,
-    response: String,
-    errors: Vec<String>,
+impl WriteOptions {
+    /// Whether the default of `field` of the record `record` is written as an integer
+    pub fn is_integral_default(&self, record: &Name, field: &str) -> bool {
+        self.integral_defaults
+            .iter()
+            .any(|(name, name_field)| name == record && name_field == field)
+    }
 }
 
-struct Protocol {
-    name: String,
-    namespace: Namespace,
-    doc: Documentation,
-    types: Vec<Types>,
-    messages: Vec<HashMap<String, Message>>
+// A whole float default as an integer, `5.0` as `5`, other values as they are
+pub(crate) fn integral_default(default: &Value) -> Value {
+    match default.as_f64() {
+        Some(float)
+            if default.is_f64() && float.fract() == 0.0 && float.abs() < i64::MAX as f64 =>
+        {
+            Value::from(float as i64)
+        }
+        _ => default.clone(),
+    }
 }
@@ -0,0 +1,78 @@
+//! Dependencies between named types, e.g. for schema registries where the
+//! schemas a schema references must be registered before it.
+
+use apache_avro::schema::{EnumSchema, FixedSchema, Name, Namespace, RecordSchema, Schema};
+
+/// The named types `schema` directly depends on through its fields and the
+/// items of its arrays, maps and unions, by fullname and without duplicates.
+pub fn schema_references(schema: &Schema) -> Vec<Name> {
+    let mut references = Vec::new();
+    match schema {
+        Schema::Record(RecordSchema { name, fields, .. }) => {
+            for field in fields {
+                collect(&field.schema, &name.namespace, &mut references);
+            }
+        }
+        Schema::Array(_) | Schema::Map(_) | Schema::Union(_) => {
+            collect(schema, &None, &mut references);
+        }
+        _ => {}
+    }
+    references
+}
+
+fn collect(schema: &Schema, enclosing_namespace: &Namespace, references: &mut Vec<Name>) {
+    match schema {
+        Schema::Record(RecordSchema { name, .. })
+        | Schema::Enum(EnumSchema { name, .. })
+        | Schema::Fixed(FixedSchema { name, .. })
+        | Schema::Ref { name } => {
+            let name = name.fully_qualified_name(enclosing_namespace);
+            if !references.contains(&name) {
+                references.push(name);
+            }
+        }
+        Schema::Array(schema) | Schema::Map(schema) => {
+            collect(schema, enclosing_namespace, references)
+        }
+        Schema::Union(union_schema) => {
+            for variant in union_schema.variants() {
+                collect(variant, enclosing_namespace, references);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_schema_references() {
+        let input = r#"@namespace("org.example")
+        protocol Shop {
+            enum Status { ACTIVE, INACTIVE }
+            record Address {
+                string street;
+            }
+            record Customer {
+                string name;
+                Status status;
+                array<Address> addresses;
+                union { null, Address } billing = null;
+            }
+        }"#;
+        let (_tail, schemas) = parse(input).unwrap();
+
+        assert_eq!(
+            schema_references(&schemas[2]),
+            vec![
+                Name::new("org.example.Status").unwrap(),
+                Name::new("org.example.Address").unwrap(),
+            ]
+        );
+        assert!(schema_references(&schemas[1]).is_empty());
+    }
+}
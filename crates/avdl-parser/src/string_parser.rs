@@ -6,7 +6,7 @@
 //! - Can contain any raw unescaped code point besides \ and "
 //! - Matches the following escape sequences: \b, \f, \n, \r, \t, \", \\, \/
 //! - Matches code points like Rust: \u{XXXX}, where XXXX can be up to 6
-//!   hex characters
+//!   hex characters, or like JSON: \uXXXX, with exactly 4 hex characters
 //! - an escape followed by whitespace consumes all whitespace between the
 //!   escape and the next non-whitespace character
 
@@ -25,8 +25,8 @@ use nom::IResult;
 // then combine them into larger parsers.
 
 /// Parse a unicode sequence, of the form u{XXXX}, where XXXX is 1 to 6
-/// hexadecimal numerals. We will combine this later with parse_escaped_char
-/// to parse sequences like \u{00AC}.
+/// hexadecimal numerals, or uXXXX as in JSON. We will combine this later with
+/// parse_escaped_char to parse sequences like \u{00AC} or \u00AC.
 fn parse_unicode<'a, E>(input: &'a str) -> IResult<&'a str, char, E>
 where
   E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseIntError>,
@@ -48,7 +48,15 @@ where
   // `map_res` takes the result of a parser and applies a function that returns
   // a Result. In this case we take the hex bytes from parse_hex and attempt to
   // convert them to a u32.
-  let parse_u32 = map_res(parse_delimited_hex, move |hex| u32::from_str_radix(hex, 16));
+  // JSON writes code points as uXXXX, with exactly 4 hex numerals
+  let parse_json_hex = preceded(
+    char('u'),
+    take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit()),
+  );
+
+  let parse_u32 = map_res(alt((parse_delimited_hex, parse_json_hex)), move |hex| {
+    u32::from_str_radix(hex, 16)
+  });
 
   // map_opt is like map_res, but it takes an Option instead of a Result. If
   // the function returns None, map_opt returns an error. In this case, because
@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+
+use apache_avro::schema::{EnumSchema, Name, RecordSchema, Schema};
+use avdl_parser::{json::to_avsc, parse_file, AvdlError};
+use serde_json::Value;
+
+fn sample(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../../tests/samples")
+        .join(name)
+}
+
+#[test]
+fn test_parse_file() {
+    let schemas = parse_file(sample("simple.avdl")).unwrap();
+
+    assert_eq!(schemas.len(), 1);
+    match &schemas[0] {
+        Schema::Record(RecordSchema { name, fields, .. }) => {
+            assert_eq!(name, &Name::new("Hello").unwrap());
+            assert_eq!(fields[0].name, "name");
+            assert_eq!(fields[0].schema, Schema::String);
+        }
+        other => panic!("expected a record, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_parse_file_missing() {
+    let err = parse_file(sample("DoesNotExist.avdl")).unwrap_err();
+    assert!(matches!(err, AvdlError::ReadError(_)));
+}
+
+#[test]
+fn test_parse_file_import_protocol() {
+    let schemas = parse_file(sample("ImportProtocol.avdl")).unwrap();
+
+    assert_eq!(schemas.len(), 2);
+    match &schemas[1] {
+        Schema::Record(RecordSchema { fields, .. }) => match &fields[0].schema {
+            Schema::Record(RecordSchema { name, fields, .. }) => {
+                assert_eq!(name, &Name::new("org.example.Greeting").unwrap());
+                assert_eq!(fields[0].schema, Schema::String);
+            }
+            other => panic!("expected the imported record, got {other:?}"),
+        },
+        other => panic!("expected a record, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_parse_file_import_idl() {
+    let schemas = parse_file(sample("ImportIdl.avdl")).unwrap();
+
+    assert_eq!(schemas.len(), 2);
+    match &schemas[1] {
+        Schema::Record(RecordSchema { fields, .. }) => {
+            match &fields[0].schema {
+                Schema::Record(RecordSchema { name, fields, .. }) => {
+                    assert_eq!(name, &Name::new("org.example.Foo").unwrap());
+                    assert_eq!(fields[0].schema, Schema::String);
+                }
+                other => panic!("expected the imported record, got {other:?}"),
+            }
+            assert_eq!(
+                fields[1].schema,
+                Schema::Array(Box::new(fields[0].schema.clone()))
+            );
+        }
+        other => panic!("expected a record, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_parse_file_import_schema() {
+    let schemas = parse_file(sample("ImportSchema.avdl")).unwrap();
+
+    assert_eq!(schemas.len(), 2);
+    match &schemas[1] {
+        Schema::Record(RecordSchema { fields, .. }) => {
+            match &fields[1].schema {
+                Schema::Record(RecordSchema { name, .. }) => {
+                    assert_eq!(name, &Name::new("org.example.Address").unwrap());
+                }
+                other => panic!("expected the imported record, got {other:?}"),
+            }
+            match &fields[2].schema {
+                Schema::Enum(EnumSchema { name, symbols, .. }) => {
+                    assert_eq!(name, &Name::new("org.example.AddressKind").unwrap());
+                    assert_eq!(symbols, &vec!["HOME", "WORK"]);
+                }
+                other => panic!("expected the imported enum, got {other:?}"),
+            }
+        }
+        other => panic!("expected a record, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_parse_file_circular_import() {
+    let err = parse_file(sample("CycleA.avdl")).unwrap_err();
+    match err {
+        AvdlError::ParseIdlError(e) => assert!(
+            e.message.contains("Circular import of"),
+            "unexpected message {}",
+            e.message
+        ),
+        other => panic!("expected a parse error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_parse_file_import_only() {
+    let schemas = parse_file(sample("Aggregate.avdl")).unwrap();
+
+    let names: Vec<String> = schemas
+        .iter()
+        .map(|schema| match schema {
+            Schema::Record(RecordSchema { name, .. }) | Schema::Enum(EnumSchema { name, .. }) => {
+                name.fullname(None)
+            }
+            other => panic!("expected a named type, got {other:?}"),
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "Kind",
+            "RecordOfRecord",
+            "MyRecordOfRecord",
+            "org.example.Address"
+        ]
+    );
+}
+
+#[test]
+fn test_to_avsc_sample() {
+    let schemas = parse_file(sample("Defaults.avdl")).unwrap();
+    let out: Value = serde_json::from_str(&to_avsc(&schemas).unwrap()).unwrap();
+
+    let expected = std::fs::read_to_string(sample("Defaults.avsc")).unwrap();
+    let expected: Value = serde_json::from_str(&expected).unwrap();
+    assert_eq!(out, expected);
+}